@@ -0,0 +1,25 @@
+//! Records the git commit being built, when there is one, for `--version`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // A new commit moves HEAD or the ref it points to, which `git gc` may
+    // have packed. Only what exists is watched, as cargo reruns the script
+    // on every build for a missing path, as in a tarball with no `.git`:
+    for path in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=PIPEBOMB_SWEEPER_COMMIT={}", commit.trim());
+    }
+}
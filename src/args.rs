@@ -0,0 +1,470 @@
+//! Command-line parsing. Named flags can be mixed with the legacy positional
+//! `rows cols density seed` arguments; a flag always wins over its positional
+//! counterpart, & either over the `PBSWEEPER_*` environment variables.
+
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pipebomb_sweeper::MAX_SIDE;
+
+use crate::config::{CursorStyle, Palette};
+
+// Delay between flood fill steps for a bare `--animate`:
+const ANIMATE_MS: u64 = 30;
+// Share of the safe cells the first open reveals with a bare `--big-start`:
+const BIG_START_PCNT: usize = 20;
+
+pub const USAGE: &str = "\
+Usage: pipebomb-sweeper [OPTIONS] [rows] [cols] [density] [seed]
+
+Options:
+  --difficulty <D>  beginner (9x9, 10 mines), intermediate (16x16, 40 mines)
+                    or expert (16x30, 99 mines)
+  --rows <N>        Board height (default 8)
+  --cols <N>        Board width (default 8)
+  --density <PCNT>  Percentage of cells holding a bomb (default 16)
+  --mines <N>       Exact number of bombs, instead of --density
+  --seed <u64>      Seed for a reproducible board (random by default)
+  --no-guess        Only generate boards that can be cleared without guessing
+  --big-start[=PCNT]
+                    Only generate boards whose first open reveals PCNT% of
+                    the safe cells, or as near as can be found (default 20)
+  --no-color        Disable colored output
+  --theme <T>       classic (default) or colorblind: colors safe for red-green
+                    color blindness, with the higher numbers underlined
+  --cursor <S>      brackets (default), reverse or bold: how the cursor's cell
+                    stands out. Reverse video & bold keep it in place
+  --compact         Draw each cell one column wide, with the cursor in reverse
+                    video, to fit bigger boards on screen
+  --training        Show the numbers of closed safe cells, dimmed, to learn
+                    reading the board from. Not recorded in best times
+                    or statistics
+  --no-help-bar     Hide the bar of keys & modes under the board
+  --bell            Ring the terminal bell when a bomb goes off
+  --no-confirm      Reset, quit & open flagged cells without asking first
+  --quiet           Only show the board & its status: no confirmations (as
+                    --no-confirm), key reminders, unknown-key notices,
+                    progress line, help bar or goodbye
+  --wrap            Let the cursor wrap around the board edges
+  --center-start    Start the cursor in the middle of the board
+  --torus           Join the opposite edges of the board, so cells on one
+                    edge neighbor those on the other (implies --wrap)
+  --win-on-flags    Also win by flagging exactly the mines
+  --lives <N>       Survive N - 1 bombs going off, each opening just that bomb
+                    (default 1)
+  --countdown <S>   Lose when S seconds of play run out before the board is
+                    cleared
+  --no-flag         No-flag (NF) mode: clear the board with opens & chords
+                    only, scored by 3BV per click
+  --daily           Play today's challenge: the same intermediate board for
+                    everyone, seeded from the UTC date & cleared around the
+                    start cell named in its title
+  --animate[=MS]    Spread openings out step by step, MS milliseconds apart
+                    (default 30)
+  --load <PATH>     Resume a game saved with 'S' (board options are ignored)
+  --resume          Resume the game autosaved when the last one was killed
+  --board <PATH>    Play a hand-made board: one line per row, '*' for a mine
+                    & '.' for a safe cell (board options are ignored)
+  --edit <PATH>     Build a board at PATH, new (sized by the board options) or
+                    loaded, with Space or f adding & removing mines & S
+                    saving it for --board
+  --code <CODE>     Play a board shared with 'e' (board options are ignored)
+  --record <PATH>   Record each game to PATH as it ends, over the last one
+  --replay <PATH>   Watch a game recorded with --record: Space pauses, n steps
+                    & q quits
+  --headless        Read commands from stdin & print plain-text boards (the
+                    default when stdin or stdout isn't a terminal). Commands:
+                    move <up|down|left|right>, edge <dir>, goto <row> <col>,
+                    open, flag, mark (flag without cycling on to ?), flag
+                    obvious, chord, solve, undo, reset, retry, quit. open,
+                    flag & chord also take a <row> <col> to move to first,
+                    where flag works like mark
+  --bot             Headless, but print a snapshot for programs after each
+                    command: state <playing|won|lost>, size <rows> <cols>,
+                    mines <n>, lives <n>, cursor <row> <col>, maybe message
+                    <text>, then a line per row of # closed, F flagged,
+                    ? questioned, 0-8 open & * exploded cells, & a blank line
+  --autosolve       Start by letting the solver play as far as it can without
+                    guessing
+  --stats           Print lifetime statistics & exit
+  --version         Print the version & exit
+  --help            Print this help
+
+The board size, density & seed default to $PBSWEEPER_ROWS, $PBSWEEPER_COLS,
+$PBSWEEPER_DENSITY & $PBSWEEPER_SEED when they're set. Run without any
+arguments in a terminal, the game starts on a title screen to pick the board.
+
+Keys:
+  WASD, hjkl, arrows  Move the cursor
+  0, $ / Home, End    Jump to the first / last column
+  g, G / PgUp, PgDn   Jump to the top / bottom row
+  :                   Go to a cell by its row & column
+  Left / right click  Open / flag a cell (middle click chords)
+  Space               Open a cell
+  f                   Cycle flag / question mark
+  m                   Flag every mine an open number gives away
+  c                   Chord: open around a satisfied number
+  ?                   Hint: jump to a provably safe cell
+  u                   Undo
+  X                   Peek at the whole board until the next key
+  !                   Let the solver play until it would have to guess
+  p                   Toggle the mine odds of every closed cell, in tenths
+  z                   Pause, hiding the board & stopping the clock
+  S                   Save the game to a file
+  e                   Show the board's code, to share it with --code
+  r                   New board
+  R                   Retry the same board
+  b                   After a game: pick the next board's difficulty
+  q, Esc              Quit
+
+The theme, cursor & bell can be set for good with a top-level
+theme = \"colorblind\", cursor = \"reverse\" & bell = true in
+$XDG_CONFIG_HOME/pipebomb-sweeper/config.toml (~/.config by default), cell
+glyphs in its [glyphs] table, the colors of the numbers 1 to 8, bomb, flag,
+cursor & closed cells in its [colors] table, by name (red, bright_blue...) or
+SGR code (\"38;5;166\"), & the up, down, left, right, open, flag, chord, reset,
+quit & hint keys in its [keys] table.";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+impl Difficulty {
+    /// `(rows, cols, mines)` of the canonical board:
+    pub fn dimensions(self) -> (usize, usize, usize) {
+        match self {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (16, 30, 99),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Intermediate => "Intermediate",
+            Difficulty::Expert => "Expert",
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "beginner" => Ok(Difficulty::Beginner),
+            "intermediate" => Ok(Difficulty::Intermediate),
+            "expert" => Ok(Difficulty::Expert),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Args {
+    pub difficulty: Option<Difficulty>,
+    pub rows: usize,
+    pub cols: usize,
+    pub density: usize,
+    pub mines: Option<usize>,
+    pub seed: Option<u64>,
+    pub no_guess: bool,
+    // Percentage of the safe cells the first open should reveal, if set:
+    pub big_start: Option<usize>,
+    pub color: bool,
+    pub theme: Option<Palette>,
+    pub cursor: Option<CursorStyle>,
+    pub compact: bool,
+    pub training: bool,
+    pub help_bar: bool,
+    pub bell: bool,
+    pub confirm: bool,
+    // Leaves out every message the game can do without:
+    pub quiet: bool,
+    pub wrap: bool,
+    pub center_start: bool,
+    pub torus: bool,
+    pub win_on_flags: bool,
+    pub no_flag: bool,
+    pub lives: u32,
+    // Seconds of play before the game is lost, if the clock counts down:
+    pub countdown: Option<u64>,
+    // Milliseconds between flood fill steps, if openings are animated:
+    pub animate: Option<u64>,
+    pub daily: bool,
+    pub load: Option<PathBuf>,
+    pub resume: bool,
+    pub board: Option<PathBuf>,
+    pub code: Option<String>,
+    pub edit: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub headless: bool,
+    pub bot: bool,
+    pub autosolve: bool,
+    pub stats: bool,
+    pub version: bool,
+    pub help: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            difficulty: None,
+            rows: 8,
+            cols: 8,
+            density: 16,
+            mines: None,
+            seed: None,
+            no_guess: false,
+            big_start: None,
+            color: true,
+            theme: None,
+            cursor: None,
+            compact: false,
+            training: false,
+            help_bar: true,
+            bell: false,
+            confirm: true,
+            quiet: false,
+            wrap: false,
+            center_start: false,
+            torus: false,
+            win_on_flags: false,
+            no_flag: false,
+            lives: 1,
+            countdown: None,
+            animate: None,
+            daily: false,
+            load: None,
+            resume: false,
+            board: None,
+            code: None,
+            edit: None,
+            record: None,
+            replay: None,
+            headless: false,
+            bot: false,
+            autosolve: false,
+            stats: false,
+            version: false,
+            help: false,
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", name))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid {}: '{}'", name, value))
+}
+
+fn next_positional<T: FromStr>(
+    positional: &mut impl Iterator<Item = String>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    positional
+        .next()
+        .map(|value| parse_value(name, Some(value)))
+        .transpose()
+}
+
+/// The value of the environment variable `name`, if it's set. One that
+/// doesn't parse or isn't `valid` is only warned about, since it may have
+/// been set long ago & far from the command line:
+fn env_value<T: FromStr>(name: &str, valid: impl Fn(&T) -> bool) -> Option<T> {
+    let value = env::var(name).ok()?;
+    match value.parse() {
+        Ok(value) if valid(&value) => Some(value),
+        _ => {
+            eprintln!("Ignoring ${}: invalid value '{}'", name, value);
+            None
+        }
+    }
+}
+
+/// True for a row or column count a board can have:
+fn is_side(&n: &usize) -> bool {
+    (1..=MAX_SIDE).contains(&n)
+}
+
+/// Parses the arguments following the program name:
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut rows = None;
+    let mut cols = None;
+    let mut density = None;
+    let mut seed = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+
+        // Accept both `--flag value` and `--flag=value`:
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (arg, None),
+        };
+        let mut value = || inline.clone().or_else(|| args.next());
+        match flag.as_str() {
+            "--difficulty" => parsed.difficulty = Some(parse_value("--difficulty", value())?),
+            "--rows" => rows = Some(parse_value("--rows", value())?),
+            "--cols" => cols = Some(parse_value("--cols", value())?),
+            "--density" => density = Some(parse_value("--density", value())?),
+            "--mines" => parsed.mines = Some(parse_value("--mines", value())?),
+            "--seed" => seed = Some(parse_value("--seed", value())?),
+            "--no-guess" => parsed.no_guess = true,
+            // Like `--animate`, the percentage only comes from `--big-start=PCNT`:
+            "--big-start" => {
+                parsed.big_start = Some(match inline.clone() {
+                    Some(pcnt) => parse_value("--big-start", Some(pcnt))?,
+                    None => BIG_START_PCNT,
+                })
+            }
+            "--no-color" => parsed.color = false,
+            "--theme" => parsed.theme = Some(parse_value("--theme", value())?),
+            "--cursor" => parsed.cursor = Some(parse_value("--cursor", value())?),
+            "--compact" => parsed.compact = true,
+            "--training" => parsed.training = true,
+            "--no-help-bar" => parsed.help_bar = false,
+            "--bell" => parsed.bell = true,
+            "--no-confirm" => parsed.confirm = false,
+            "--quiet" => {
+                parsed.quiet = true;
+                parsed.confirm = false;
+            }
+            "--wrap" => parsed.wrap = true,
+            "--center-start" => parsed.center_start = true,
+            "--torus" => parsed.torus = true,
+            "--win-on-flags" => parsed.win_on_flags = true,
+            "--no-flag" => parsed.no_flag = true,
+            "--lives" => parsed.lives = parse_value("--lives", value())?,
+            "--countdown" => parsed.countdown = Some(parse_value("--countdown", value())?),
+            // The delay is optional, so it's only taken from `--animate=MS`:
+            "--animate" => {
+                parsed.animate = Some(match inline.clone() {
+                    Some(ms) => parse_value("--animate", Some(ms))?,
+                    None => ANIMATE_MS,
+                })
+            }
+            "--daily" => parsed.daily = true,
+            "--load" => parsed.load = Some(parse_value("--load", value())?),
+            "--resume" => parsed.resume = true,
+            "--board" => parsed.board = Some(parse_value("--board", value())?),
+            "--code" => parsed.code = Some(parse_value("--code", value())?),
+            "--edit" => parsed.edit = Some(parse_value("--edit", value())?),
+            "--record" => parsed.record = Some(parse_value("--record", value())?),
+            "--replay" => parsed.replay = Some(parse_value("--replay", value())?),
+            "--headless" => parsed.headless = true,
+            "--bot" => parsed.bot = true,
+            "--autosolve" => parsed.autosolve = true,
+            "--stats" => parsed.stats = true,
+            "--version" => parsed.version = true,
+            "--help" => parsed.help = true,
+            _ => return Err(format!("Unknown option: '{}'", flag)),
+        }
+    }
+
+    if [
+        parsed.load.is_some(),
+        parsed.resume,
+        parsed.board.is_some(),
+        parsed.code.is_some(),
+        parsed.edit.is_some(),
+        parsed.replay.is_some(),
+    ]
+    .iter()
+    .filter(|&&given| given)
+    .count()
+        > 1
+    {
+        return Err(
+            "Only one of --load, --resume, --board, --code, --edit and --replay can be used"
+                .to_owned(),
+        );
+    }
+    if parsed.lives == 0 {
+        return Err("--lives must be at least 1".to_owned());
+    }
+    if parsed.countdown == Some(0) {
+        return Err("--countdown must be at least 1 second".to_owned());
+    }
+    if parsed.edit.is_some() && (parsed.headless || parsed.bot) {
+        return Err("--edit can't be used with --headless or --bot".to_owned());
+    }
+    if parsed.no_guess && parsed.big_start.is_some() {
+        return Err("--no-guess and --big-start can't be used together".to_owned());
+    }
+    if parsed.no_flag && parsed.win_on_flags {
+        return Err("--no-flag and --win-on-flags can't be used together".to_owned());
+    }
+    if density.is_some() && parsed.mines.is_some() {
+        return Err("--density and --mines can't be used together".to_owned());
+    }
+    if parsed.daily {
+        // The date picks the whole board, seed included:
+        if parsed.difficulty.is_some()
+            || rows.is_some()
+            || cols.is_some()
+            || density.is_some()
+            || parsed.mines.is_some()
+            || seed.is_some()
+            || !positional.is_empty()
+            || parsed.load.is_some()
+            || parsed.resume
+            || parsed.board.is_some()
+            || parsed.code.is_some()
+            || parsed.edit.is_some()
+            || parsed.replay.is_some()
+        {
+            return Err("--daily can't be combined with other board options".to_owned());
+        }
+        parsed.difficulty = Some(crate::daily::DIFFICULTY);
+    }
+    if let Some(difficulty) = parsed.difficulty {
+        // A preset fixes the whole board, so any explicit size is ambiguous:
+        if rows.is_some() || cols.is_some() || density.is_some() || parsed.mines.is_some() {
+            return Err(
+                "--difficulty can't be combined with --rows, --cols, --density or --mines"
+                    .to_owned(),
+            );
+        }
+        if let Some(arg) = positional.first() {
+            return Err(format!("Unexpected argument with --difficulty: '{}'", arg));
+        }
+        let (preset_rows, preset_cols, preset_mines) = difficulty.dimensions();
+        rows = Some(preset_rows);
+        cols = Some(preset_cols);
+        parsed.mines = Some(preset_mines);
+    }
+    if positional.len() > 4 {
+        return Err(format!("Unexpected argument: '{}'", positional[4]));
+    }
+    // Positional arguments are still consumed in order, but flags win:
+    let mut positional = positional.into_iter();
+    parsed.rows = rows
+        .or(next_positional(&mut positional, "rows")?)
+        .or_else(|| env_value("PBSWEEPER_ROWS", is_side))
+        .unwrap_or(parsed.rows);
+    parsed.cols = cols
+        .or(next_positional(&mut positional, "cols")?)
+        .or_else(|| env_value("PBSWEEPER_COLS", is_side))
+        .unwrap_or(parsed.cols);
+    parsed.density = density
+        .or(next_positional(&mut positional, "density")?)
+        .or_else(|| env_value("PBSWEEPER_DENSITY", |_| true))
+        .unwrap_or(parsed.density);
+    parsed.seed = seed
+        .or(next_positional(&mut positional, "seed")?)
+        .or_else(|| env_value("PBSWEEPER_SEED", |_| true));
+    Ok(parsed)
+}
@@ -0,0 +1,163 @@
+//! Board codes: a short, pasteable string for sharing a board. It's the
+//! URL-safe base64 (unpadded) of a version byte, the size & mine count, the
+//! seed, and, once the bombs are placed, a bitmap of them a row at a time,
+//! most significant bit first. A code taken before the first open has no
+//! bitmap, & gives the same bombs from the same first open.
+
+use crate::{check_dimensions, Field};
+
+const VERSION: u8 = 1;
+// Version, rows & cols (u16), mines (u32), seed (u64) & whether the layout
+// follows:
+const HEADER_LEN: usize = 1 + 2 + 2 + 4 + 8 + 1;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        // n bytes take n + 1 characters:
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    text
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return Err("The code is cut short".to_owned());
+        }
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(|| format!("Invalid character in the code: '{}'", c as char))?;
+            bits |= (value as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+impl Field {
+    /// The code to share this board with, see the module docs:
+    pub fn to_code(&self) -> String {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.cells.len().div_ceil(8));
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.rows as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.cols as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.bomb_count as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        // Hand-made boards have their bombs from the start:
+        let placed = self.fixed || !self.first_move;
+        bytes.push(placed as u8);
+        if placed {
+            for cells in self.cells.chunks(8) {
+                let byte = cells.iter().enumerate().fold(0u8, |byte, (i, cell)| {
+                    byte | (cell.pipebomb as u8) << (7 - i)
+                });
+                bytes.push(byte);
+            }
+        }
+        encode(&bytes)
+    }
+
+    /// The board a `to_code` code stands for, closed & ready to play. Boards
+    /// with their bombs placed come back as hand-made ones:
+    pub fn from_code(code: &str) -> Result<Field, String> {
+        let bytes = decode(code.trim())?;
+        if bytes.len() < HEADER_LEN {
+            return Err("The code is cut short".to_owned());
+        }
+        if bytes[0] != VERSION {
+            return Err(format!("Unknown code version {}", bytes[0]));
+        }
+        let rows = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        let cols = u16::from_be_bytes([bytes[3], bytes[4]]) as usize;
+        let mines = u32::from_be_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        let seed = u64::from_be_bytes(bytes[9..17].try_into().unwrap());
+        // Checked before the size is trusted with an allocation:
+        check_dimensions(rows, cols)?;
+        let layout = &bytes[HEADER_LEN..];
+        match bytes[17] {
+            0 if layout.is_empty() => Field::try_new(rows, cols, 0, Some(mines), seed),
+            1 if layout.len() == (rows * cols).div_ceil(8) => {
+                let bombs: Vec<_> = (0..rows * cols)
+                    .filter(|&i| layout[i / 8] & 0x80 >> (i % 8) != 0)
+                    .map(|i| (i / cols, i % cols))
+                    .collect();
+                if bombs.len() != mines {
+                    return Err("The layout doesn't match the mine count".to_owned());
+                }
+                if mines == rows * cols {
+                    return Err("The board has no safe cell".to_owned());
+                }
+                Ok(Field::with_bombs(rows, cols, &bombs, seed))
+            }
+            _ => Err("The code's layout doesn't fit the board".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_survive_encoding_at_every_length() {
+        let bytes: Vec<u8> = (0..=255).rev().collect();
+        for len in 0..12 {
+            assert_eq!(decode(&encode(&bytes[..len])).unwrap(), &bytes[..len]);
+        }
+        assert!(decode("A").is_err());
+        assert!(decode("AB=C").is_err());
+    }
+
+    #[test]
+    fn a_fresh_board_comes_back_with_the_same_first_open() {
+        let mut field = Field::new(9, 13, 0, Some(20), 1234);
+        let mut copy = Field::from_code(&field.to_code()).unwrap();
+        assert_eq!((copy.rows(), copy.cols()), (9, 13));
+        assert_eq!((copy.bomb_count(), copy.seed()), (20, 1234));
+        assert!(copy.is_first_move());
+        field.place_bombs_excluding(4, 6);
+        copy.place_bombs_excluding(4, 6);
+        assert_eq!(field.to_ascii(), copy.to_ascii());
+        assert_eq!(field.bomb_layout(), copy.bomb_layout());
+    }
+
+    #[test]
+    fn a_played_board_comes_back_with_its_bombs() {
+        let mut field = Field::new(7, 10, 0, Some(15), 99);
+        field.place_bombs_excluding(3, 3);
+        let mut copy = Field::from_code(&field.to_code()).unwrap();
+        assert_eq!(field.bomb_layout(), copy.bomb_layout());
+        // The bombs stay put, wherever the replay opens first:
+        copy.place_bombs_excluding(0, 0);
+        assert_eq!(field.bomb_layout(), copy.bomb_layout());
+        assert_eq!(copy.to_code(), field.to_code());
+    }
+
+    #[test]
+    fn broken_codes_are_rejected() {
+        let code = Field::new(5, 5, 0, Some(3), 0).to_code();
+        assert!(Field::from_code(&code[..code.len() - 4]).is_err());
+        assert!(Field::from_code(&format!("B{}", &code[1..])).is_err());
+        assert!(Field::from_code("not a code!").is_err());
+        // Every cell mined leaves nothing to open:
+        let full = Field::with_bombs(2, 2, &[(0, 0), (0, 1), (1, 0), (1, 1)], 0);
+        assert_eq!(
+            Field::from_code(&full.to_code()).err().as_deref(),
+            Some("The board has no safe cell")
+        );
+    }
+}
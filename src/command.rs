@@ -0,0 +1,254 @@
+//! Game commands shared by the interactive key bindings & the headless mode,
+//! which reads them one per line as text (`move down`, `open`, `goto 3 4`...).
+
+use std::fmt;
+use std::str::FromStr;
+
+use pipebomb_sweeper::solver::{self, Progress};
+use pipebomb_sweeper::{Field, Orientation};
+
+// How many layouts `--no-guess` tries before settling for one that needs a guess:
+const NO_GUESS_ATTEMPTS: usize = 500;
+// How many layouts `--big-start` tries before settling for the biggest opening:
+const BIG_START_ATTEMPTS: usize = 100;
+
+/// How the bombs are laid out on the first open:
+#[derive(Clone, Copy)]
+pub enum Placement {
+    /// Anywhere but around the first cell:
+    Random,
+    /// Only where the board can be cleared without guessing, if possible:
+    NoGuess,
+    /// Only where the first open reveals at least this percentage of the
+    /// safe cells, or as near as can be found:
+    BigStart(usize),
+}
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy)]
+pub enum Command {
+    Move(Direction),
+    /// Jumps to the board edge in a direction:
+    Edge(Direction),
+    GoTo(usize, usize),
+    Open,
+    Flag,
+    /// Flags the cell under the cursor, leaving a flag in place rather than
+    /// cycling it on to a question mark:
+    Mark,
+    /// Flags every cell that an open number proves is a bomb:
+    FlagObvious,
+    Chord,
+    Undo,
+    /// Starts a new board:
+    Reset,
+    /// Replays the current board from scratch, bombs unchanged:
+    Retry,
+    /// Plays by deduction until the board is clear or needs a guess,
+    /// opening at the cursor first on a fresh board:
+    Solve,
+}
+
+/// What a command did, beyond changing the field:
+#[derive(PartialEq)]
+pub enum Outcome {
+    Done,
+    /// A bomb went off:
+    Exploded,
+    /// A bomb went off, but a life was left to spend on it:
+    LostLife,
+    /// `--no-guess` gave up & placed a board that may need a guess:
+    MayNeedGuess,
+    NoSuchCell,
+    /// The solver stopped where only a guess would get further:
+    Stuck,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            "left" => Ok(Direction::Left),
+            "right" => Ok(Direction::Right),
+            _ => Err(format!("Unknown direction: '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        })
+    }
+}
+
+/// The text `from_str` reads back as the same command:
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Move(direction) => write!(f, "move {}", direction),
+            Command::Edge(direction) => write!(f, "edge {}", direction),
+            Command::GoTo(row, col) => write!(f, "goto {} {}", row, col),
+            Command::Open => f.write_str("open"),
+            Command::Flag => f.write_str("flag"),
+            Command::Mark => f.write_str("mark"),
+            Command::FlagObvious => f.write_str("flag obvious"),
+            Command::Chord => f.write_str("chord"),
+            Command::Undo => f.write_str("undo"),
+            Command::Reset => f.write_str("reset"),
+            Command::Retry => f.write_str("retry"),
+            Command::Solve => f.write_str("solve"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let coordinate = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|_| format!("Invalid coordinate: '{}'", word))
+        };
+        match words.as_slice() {
+            ["move", direction] => Ok(Command::Move(direction.parse()?)),
+            ["edge", direction] => Ok(Command::Edge(direction.parse()?)),
+            ["goto", row, col] => Ok(Command::GoTo(coordinate(row)?, coordinate(col)?)),
+            ["open"] => Ok(Command::Open),
+            ["flag"] => Ok(Command::Flag),
+            ["mark"] => Ok(Command::Mark),
+            ["flag", "obvious"] => Ok(Command::FlagObvious),
+            ["chord"] => Ok(Command::Chord),
+            ["undo"] => Ok(Command::Undo),
+            ["reset"] => Ok(Command::Reset),
+            ["retry"] => Ok(Command::Retry),
+            ["solve"] => Ok(Command::Solve),
+            _ => Err(format!("Unknown command: '{}'", s.trim())),
+        }
+    }
+}
+
+/// Parses a line of the headless mode: one command, or `open`, `flag` or
+/// `chord` followed by the row & column to move to first, as a bot would
+/// send them. A bot's `flag` only ever flags, so sending it twice is
+/// harmless. Case doesn't matter:
+pub fn parse_line(line: &str) -> Result<Vec<Command>, String> {
+    let line = line.to_lowercase();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [action @ ("open" | "flag" | "chord"), row, col] => Ok(vec![
+            format!("goto {} {}", row, col).parse()?,
+            match *action {
+                "flag" => Command::Mark,
+                action => action.parse()?,
+            },
+        ]),
+        _ => Ok(vec![line.parse()?]),
+    }
+}
+
+/// Opens the cell under the cursor, placing the bombs first if it's the
+/// first open:
+fn open(field: &mut Field, placement: Placement, on_ring: impl FnMut(&Field)) -> Outcome {
+    let [row, col] = field.cursor();
+    let mut outcome = Outcome::Done;
+    if field.is_first_move() {
+        match placement {
+            Placement::Random => field.place_bombs_excluding(row, col),
+            Placement::NoGuess => {
+                if !field.place_bombs_no_guess(row, col, NO_GUESS_ATTEMPTS) {
+                    outcome = Outcome::MayNeedGuess;
+                }
+            }
+            Placement::BigStart(percent) => {
+                field.place_bombs_big_start(row, col, percent, BIG_START_ATTEMPTS);
+            }
+        }
+    }
+    if field.open_at_cursor_with(on_ring) {
+        return explode(field);
+    }
+    outcome
+}
+
+/// The outcome of a bomb going off, once it's had a chance to cost a life
+/// instead of the game:
+fn explode(field: &mut Field) -> Outcome {
+    if field.survive_explosion() {
+        Outcome::LostLife
+    } else {
+        Outcome::Exploded
+    }
+}
+
+/// Runs `command` against `field`. The first open places the bombs, around
+/// the cursor, as `placement` has them. `on_ring` sees
+/// the field after each step of an open's flood fill, or of the solver:
+pub fn apply_command(
+    field: &mut Field,
+    command: Command,
+    placement: Placement,
+    mut on_ring: impl FnMut(&Field),
+) -> Outcome {
+    let [row, col] = field.cursor();
+    match command {
+        Command::Move(Direction::Up) => field.dec_cursor(Orientation::Vertical),
+        Command::Move(Direction::Down) => field.inc_cursor(Orientation::Vertical),
+        Command::Move(Direction::Left) => field.dec_cursor(Orientation::Horizontal),
+        Command::Move(Direction::Right) => field.inc_cursor(Orientation::Horizontal),
+        Command::Edge(Direction::Up) => field.set_cursor(0, col),
+        Command::Edge(Direction::Down) => field.set_cursor(field.rows() - 1, col),
+        Command::Edge(Direction::Left) => field.set_cursor(row, 0),
+        Command::Edge(Direction::Right) => field.set_cursor(row, field.cols() - 1),
+        Command::GoTo(row, col) => {
+            if row >= field.rows() || col >= field.cols() {
+                return Outcome::NoSuchCell;
+            }
+            field.set_cursor(row, col);
+        }
+        Command::Open => return open(field, placement, on_ring),
+        Command::Flag => field.flag_at_cursor(),
+        Command::Mark => field.flag_at(row, col),
+        Command::FlagObvious => {
+            field.flag_obvious_mines();
+        }
+        Command::Chord => {
+            if field.chord_at_cursor() {
+                return explode(field);
+            }
+        }
+        Command::Undo => {
+            field.undo();
+        }
+        Command::Reset => field.new_board(),
+        Command::Retry => field.reset_states(),
+        Command::Solve => {
+            if field.is_first_move() && open(field, placement, &mut on_ring) == Outcome::Exploded {
+                return Outcome::Exploded;
+            }
+            loop {
+                match solver::solve_step(field) {
+                    Progress::Advanced => on_ring(field),
+                    Progress::Solved => break,
+                    Progress::Stuck => return Outcome::Stuck,
+                }
+            }
+        }
+    }
+    Outcome::Done
+}
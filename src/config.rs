@@ -0,0 +1,581 @@
+//! The optional config file, `$XDG_CONFIG_HOME/pipebomb-sweeper/config.toml`
+//! (falling back to `~/.config`). Only the bit of TOML it needs is understood:
+//! `[table]` headers, `key = "string"` & `key = true/false` pairs & `#`
+//! comments. Anything left out keeps its default.
+//!
+//! ```toml
+//! theme = "colorblind"
+//! cursor = "reverse"
+//! bell = true
+//!
+//! [glyphs]
+//! bomb = "*"
+//! flag = "F"
+//! closed = "#"
+//! question = "?"
+//! cursor_left = "<"
+//! cursor_right = ">"
+//!
+//! [colors]
+//! 1 = "bright_blue"
+//! 3 = "1;31"
+//! bomb = "38;5;166"
+//! closed = "gray"
+//!
+//! [keys]
+//! up = "i"
+//! left = "j"
+//! down = "k"
+//! right = "l"
+//! flag = "e"
+//! ```
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::width;
+
+/// The colors cells are drawn in:
+#[derive(Clone, Copy, Default)]
+pub enum Palette {
+    #[default]
+    Classic,
+    // Safe for red-green color blindness:
+    Colorblind,
+}
+
+impl FromStr for Palette {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Palette::Classic),
+            "colorblind" => Ok(Palette::Colorblind),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How the cursor's cell stands out:
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum CursorStyle {
+    #[default]
+    Brackets,
+    Reverse,
+    Bold,
+}
+
+impl FromStr for CursorStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "brackets" => Ok(CursorStyle::Brackets),
+            "reverse" => Ok(CursorStyle::Reverse),
+            "bold" => Ok(CursorStyle::Bold),
+            _ => Err(()),
+        }
+    }
+}
+
+/// SGR codes from the `[colors]` table, each drawn instead of the palette's.
+/// Closed cells & the cursor have no color unless one is set here:
+#[derive(Default)]
+pub struct ColorTable {
+    // Indexed by adjacent-bomb count - 1:
+    pub numbers: [Option<String>; 8],
+    pub bomb: Option<String>,
+    pub flag: Option<String>,
+    pub cursor: Option<String>,
+    pub closed: Option<String>,
+}
+
+/// The SGR code for a color given by name (`red`, `bright_blue`...) or as
+/// its code (`"94"`, `"1;38;5;25"`):
+fn parse_color(value: &str) -> Result<String, String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let name = value.to_lowercase();
+    let (base, name) = match name.strip_prefix("bright_") {
+        Some(name) => (90, name),
+        None => (30, name.as_str()),
+    };
+    if let Some(i) = NAMES.iter().position(|&known| known == name) {
+        return Ok((base + i).to_string());
+    }
+    if name == "gray" || name == "grey" {
+        return Ok("90".to_owned());
+    }
+    let is_code = value
+        .split(';')
+        .all(|part| !part.is_empty() && part.len() <= 3 && part.parse::<u8>().is_ok());
+    if is_code {
+        Ok(value.to_owned())
+    } else {
+        Err(format!("unknown color '{}'", value))
+    }
+}
+
+/// What cells & the cursor look like:
+pub struct Theme {
+    pub palette: Palette,
+    pub bomb: String,
+    pub flag: String,
+    pub closed: String,
+    pub question: String,
+    pub cursor_left: String,
+    pub cursor_right: String,
+    pub cursor: CursorStyle,
+    pub colors: ColorTable,
+    // One column per cell, which leaves no room for the cursor's brackets:
+    pub compact: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            palette: Palette::default(),
+            bomb: "@".to_owned(),
+            flag: ">".to_owned(),
+            closed: ".".to_owned(),
+            question: "?".to_owned(),
+            cursor_left: "[".to_owned(),
+            cursor_right: "]".to_owned(),
+            cursor: CursorStyle::default(),
+            colors: ColorTable::default(),
+            compact: false,
+        }
+    }
+}
+
+impl Theme {
+    /// How the cursor is drawn. Compact cells have no room for brackets, so
+    /// they get reverse video instead:
+    pub fn cursor_style(&self) -> CursorStyle {
+        match self.cursor {
+            CursorStyle::Brackets if self.compact => CursorStyle::Reverse,
+            style => style,
+        }
+    }
+
+    /// Columns for each of the cursor's brackets, none if compact:
+    pub fn bracket_width(&self) -> usize {
+        if self.compact {
+            return 0;
+        }
+        width::width(&self.cursor_left).max(width::width(&self.cursor_right))
+    }
+
+    /// Columns for the glyph between the brackets. Numbers & the misflag
+    /// mark take one:
+    pub fn glyph_width(&self) -> usize {
+        [&self.bomb, &self.flag, &self.closed, &self.question]
+            .iter()
+            .map(|glyph| width::width(glyph))
+            .fold(1, usize::max)
+    }
+
+    /// Columns each cell of the grid takes:
+    pub fn cell_width(&self) -> usize {
+        2 * self.bracket_width() + self.glyph_width()
+    }
+}
+
+/// Game actions that can be bound to other keys:
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Open,
+    Flag,
+    Chord,
+    Reset,
+    Quit,
+    Hint,
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "up" => Action::Up,
+            "down" => Action::Down,
+            "left" => Action::Left,
+            "right" => Action::Right,
+            "open" => Action::Open,
+            "flag" => Action::Flag,
+            "chord" => Action::Chord,
+            "reset" => Action::Reset,
+            "quit" => Action::Quit,
+            "hint" => Action::Hint,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Open => "open",
+            Action::Flag => "flag",
+            Action::Chord => "chord",
+            Action::Reset => "reset",
+            Action::Quit => "quit",
+            Action::Hint => "hint",
+        }
+    }
+
+    /// The built-in key that does this action:
+    fn default_key(self) -> char {
+        match self {
+            Action::Up => 'w',
+            Action::Down => 's',
+            Action::Left => 'a',
+            Action::Right => 'd',
+            Action::Open => ' ',
+            Action::Flag => 'f',
+            Action::Chord => 'c',
+            Action::Reset => 'r',
+            Action::Quit => 'q',
+            Action::Hint => '?',
+        }
+    }
+}
+
+/// Keys bound in the `[keys]` table. They take over from whatever they did
+/// by default, while the built-in keys of their actions keep working too:
+#[derive(Default)]
+pub struct KeyMap {
+    bindings: HashMap<char, Action>,
+}
+
+impl KeyMap {
+    /// The built-in key for whatever `key` is bound to, or `key` itself:
+    pub fn translate(&self, key: char) -> char {
+        self.bindings
+            .get(&key)
+            .map_or(key, |action| action.default_key())
+    }
+
+    /// The key bound to `action`, or its built-in key if none is:
+    fn key_for(&self, action: Action) -> char {
+        self.bindings
+            .iter()
+            .filter(|&(_, &bound)| bound == action)
+            .map(|(&key, _)| key)
+            .min()
+            .unwrap_or(action.default_key())
+    }
+
+    /// The keys for the common actions, as bound, in a line for the help bar:
+    pub fn help(&self) -> String {
+        let key = |action| match self.key_for(action) {
+            ' ' => "Space".to_owned(),
+            key => key.to_string(),
+        };
+        let moves = [Action::Up, Action::Left, Action::Down, Action::Right];
+        let moves = if moves
+            .iter()
+            .all(|&action| self.key_for(action) == action.default_key())
+        {
+            "WASD/hjkl".to_owned()
+        } else {
+            moves.into_iter().map(key).collect()
+        };
+        format!(
+            "Move: {}  Open: {}  Flag: {}  Chord: {}  Hint: {}  New: {}  Quit: {}",
+            moves,
+            key(Action::Open),
+            key(Action::Flag),
+            key(Action::Chord),
+            key(Action::Hint),
+            key(Action::Reset),
+            key(Action::Quit)
+        )
+    }
+
+    fn bind(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let action = Action::from_name(name).ok_or_else(|| "unknown key".to_owned())?;
+        // Keys are read a byte at a time:
+        let mut chars = value.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(key), None) if key.is_ascii() && !key.is_ascii_control() => key,
+            _ => return Err("must be a single ASCII character".to_owned()),
+        };
+        match self.bindings.get(&key) {
+            Some(&bound) if bound != action => Err(format!(
+                "'{}' is bound to both {} & {}",
+                key,
+                bound.name(),
+                name
+            )),
+            _ => {
+                self.bindings.insert(key, action);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub theme: Theme,
+    pub keys: KeyMap,
+    // Rings the terminal bell when a bomb goes off:
+    pub bell: bool,
+}
+
+/// `$XDG_CONFIG_HOME/pipebomb-sweeper/config.toml`, falling back to
+/// `~/.config`:
+fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("pipebomb-sweeper").join("config.toml"))
+}
+
+/// What's wrong with the config file, & where:
+pub struct ConfigError {
+    path: PathBuf,
+    // 1-based, unless the file couldn't be read at all:
+    line: Option<usize>,
+    // The key whose value is wrong, as `table.key`:
+    key: Option<String>,
+    message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, ": {}", key)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Reads the config file. A missing file is the default config:
+pub fn load() -> Result<Config, ConfigError> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text, &path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(ConfigError {
+            path,
+            line: None,
+            key: None,
+            message: e.to_string(),
+        }),
+    }
+}
+
+/// The contents of a `"..."` string, with `\"` & `\\` escapes:
+fn parse_string(value: &str) -> Option<String> {
+    let body = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut text = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\') => text.push(c),
+                _ => return None,
+            },
+            '"' => return None,
+            c => text.push(c),
+        }
+    }
+    Some(text)
+}
+
+/// Strips a trailing `#` comment, minding `#`s inside strings:
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse(text: &str, path: &Path) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    let mut table = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let at = |key: Option<String>, message: String| ConfigError {
+            path: path.to_owned(),
+            line: Some(i + 1),
+            key,
+            message,
+        };
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if !matches!(name, "glyphs" | "keys" | "colors") {
+                return Err(at(None, format!("unknown table [{}]", name)));
+            }
+            table = name.to_owned();
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| at(None, format!("expected 'key = value', got '{}'", line)))?;
+        let (key, value) = (key.trim(), value.trim());
+        let qualified = match table.as_str() {
+            "" => key.to_owned(),
+            table => format!("{}.{}", table, key),
+        };
+        if table.is_empty() && key == "bell" {
+            config.bell = match value {
+                "true" => true,
+                "false" => false,
+                _ => return Err(at(Some(qualified), "must be true or false".to_owned())),
+            };
+            continue;
+        }
+        let value = parse_string(value)
+            .ok_or_else(|| at(Some(qualified.clone()), "invalid string".to_owned()))?;
+        match table.as_str() {
+            "" if key == "theme" => value
+                .parse()
+                .map(|palette| config.theme.palette = palette)
+                .map_err(|_| format!("unknown theme '{}'", value)),
+            "" if key == "cursor" => value
+                .parse()
+                .map(|style| config.theme.cursor = style)
+                .map_err(|_| format!("unknown cursor style '{}'", value)),
+            "glyphs" => set_glyph(&mut config.theme, key, value),
+            "colors" => set_color(&mut config.theme.colors, key, &value),
+            "keys" => config.keys.bind(key, &value),
+            _ => Err("unknown key, or one that belongs in a table".to_owned()),
+        }
+        .map_err(|message| at(Some(qualified), message))?;
+    }
+    Ok(config)
+}
+
+fn set_glyph(theme: &mut Theme, key: &str, value: String) -> Result<(), String> {
+    let glyph = match key {
+        "bomb" => &mut theme.bomb,
+        "flag" => &mut theme.flag,
+        "closed" => &mut theme.closed,
+        "question" => &mut theme.question,
+        "cursor_left" => &mut theme.cursor_left,
+        "cursor_right" => &mut theme.cursor_right,
+        _ => return Err("unknown key".to_owned()),
+    };
+    // Cells widen to fit the glyphs, but only so far:
+    if !(1..=2).contains(&width::width(&value)) {
+        return Err("must be one or two columns wide".to_owned());
+    }
+    *glyph = value;
+    Ok(())
+}
+
+fn set_color(colors: &mut ColorTable, key: &str, value: &str) -> Result<(), String> {
+    let color = match key {
+        "bomb" => &mut colors.bomb,
+        "flag" => &mut colors.flag,
+        "cursor" => &mut colors.cursor,
+        "closed" => &mut colors.closed,
+        _ => match key.parse::<usize>() {
+            Ok(n @ 1..=8) => &mut colors.numbers[n - 1],
+            _ => return Err("unknown key".to_owned()),
+        },
+    };
+    *color = Some(parse_color(value)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_text(text: &str) -> Result<Config, ConfigError> {
+        parse(text, Path::new("config.toml"))
+    }
+
+    #[test]
+    fn tables_set_glyphs_keys_and_colors() {
+        let config = parse_text(
+            "# A comment\n\
+             theme = \"colorblind\"\n\
+             bell = true\n\
+             \n\
+             [glyphs]\n\
+             bomb = \"💣\"  # two columns\n\
+             flag = \"#\"\n\
+             \n\
+             [keys]\n\
+             flag = \"e\"\n\
+             \n\
+             [colors]\n\
+             1 = \"bright_blue\"\n\
+             closed = \"1;38;5;25\"\n",
+        )
+        .ok()
+        .unwrap();
+        assert!(config.bell);
+        assert!(matches!(config.theme.palette, Palette::Colorblind));
+        assert_eq!(config.theme.bomb, "💣");
+        assert_eq!(config.theme.flag, "#");
+        assert_eq!(config.theme.glyph_width(), 2);
+        assert_eq!(config.keys.translate('e'), Action::Flag.default_key());
+        assert_eq!(config.theme.colors.numbers[0].as_deref(), Some("94"));
+        assert_eq!(config.theme.colors.closed.as_deref(), Some("1;38;5;25"));
+    }
+
+    #[test]
+    fn strings_keep_escaped_quotes_and_hashes() {
+        let config = parse_text("[glyphs]\nclosed = \"\\\"\"\nflag = \"#\"\n")
+            .ok()
+            .unwrap();
+        assert_eq!(config.theme.closed, "\"");
+        assert_eq!(config.theme.flag, "#");
+    }
+
+    #[test]
+    fn errors_name_the_line_and_key() {
+        let error = |text| parse_text(text).err().unwrap().to_string();
+        assert_eq!(
+            error("bell = true\n\n[glyphs]\nbomb = \"***\"\n"),
+            "config.toml:4: glyphs.bomb: must be one or two columns wide"
+        );
+        assert_eq!(
+            error("[keys]\nflag = \"e\"\nopen = \"e\"\n"),
+            "config.toml:3: keys.open: 'e' is bound to both flag & open"
+        );
+        assert_eq!(
+            error("[colors]\n9 = \"red\"\n"),
+            "config.toml:2: colors.9: unknown key"
+        );
+        assert_eq!(error("[sounds]\n"), "config.toml:1: unknown table [sounds]");
+        assert_eq!(
+            error("bell = \"yes\"\n"),
+            "config.toml:1: bell: must be true or false"
+        );
+    }
+}
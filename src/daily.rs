@@ -0,0 +1,47 @@
+//! The daily challenge: one board a day, the same for everyone, seeded from
+//! the UTC date. Its bombs are laid out before the first open, clear of an
+//! announced start cell, so where players open first doesn't change them.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Every daily board is played at this difficulty:
+pub const DIFFICULTY: crate::args::Difficulty = crate::args::Difficulty::Intermediate;
+
+/// The cell the board is cleared around, for everyone to open first: the
+/// middle one, where `--center-start` puts the cursor. As `(row, col)`:
+pub fn start() -> (usize, usize) {
+    let (rows, cols, _) = DIFFICULTY.dimensions();
+    (rows / 2, cols / 2)
+}
+
+/// Today's UTC date as `YYYY-MM-DD`:
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date, after
+/// Howard Hinnant's `civil_from_days`:
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// FNV-1a hash of `date`. Unlike std's hashers it's fixed, so every build
+/// agrees on the day's board:
+pub fn seed(date: &str) -> u64 {
+    date.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -0,0 +1,1420 @@
+//! Core Minesweeper rules for pipebomb-sweeper. Nothing in here prints or
+//! reads from the terminal, so alternate front-ends can drive a `Field`
+//! directly.
+
+mod code;
+pub mod save;
+pub mod solver;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// How many actions `Field::undo` can step back through:
+const HISTORY_LIMIT: usize = 100;
+
+/// Largest number of rows or columns a field can have. Keeps the cell count &
+/// mine arithmetic far from overflowing & the grid a sane size in memory:
+pub const MAX_SIDE: usize = 1000;
+
+/// Checks that a `rows x cols` field can be created:
+pub fn check_dimensions(rows: usize, cols: usize) -> Result<(), String> {
+    if rows == 0 || cols == 0 {
+        return Err("The board needs at least one row and one column".to_owned());
+    }
+    if rows > MAX_SIDE || cols > MAX_SIDE {
+        return Err(format!(
+            "The board can't be larger than {0}x{0}, got {1}x{2}",
+            MAX_SIDE, rows, cols
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Open,
+    Closed,
+    Flagged,
+    Questioned,
+}
+
+pub enum Orientation {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub state: State,
+    pub pipebomb: bool,
+    // Bombs in the surrounding cells, cached whenever bombs are placed:
+    pub adjacent: u8,
+}
+
+impl Cell {
+    pub fn empty() -> Self {
+        Cell {
+            state: State::Closed,
+            pipebomb: false,
+            adjacent: 0,
+        }
+    }
+}
+
+pub struct Field {
+    rows: usize,
+    cols: usize,
+    // Row-major, `rows * cols` long:
+    cells: Vec<Cell>,
+    bomb_count: usize,
+    // Safe cells not opened yet; the game is won when this reaches zero:
+    unopened_safe_cells: usize,
+    // Safe cells opened & cells flagged, kept in step by `write_state`:
+    opened: usize,
+    flags: usize,
+    // Opens, chords & flag changes this game that changed the board, a flood
+    // fill counting as the one click it was:
+    actions: usize,
+    // Set by `begin_action` until the action changes its first cell:
+    action_pending: bool,
+    // The board's 3BV, counted whenever bombs are placed:
+    three_bv: usize,
+    // Bumped by every change to the cells, so what's worked out from them
+    // can tell when it's out of date:
+    generation: u64,
+    cursor: [usize; 2],
+    // Each game starts with the cursor mid-board rather than in the corner:
+    center_start: bool,
+    // Moving the cursor past an edge jumps to the opposite edge:
+    wrap: bool,
+    // The edges meet, top to bottom & left to right, so edge cells have
+    // neighbors on the far side:
+    torus: bool,
+    // Also win once exactly the bombs are flagged:
+    win_on_flags: bool,
+    // Bombs that can still go off before the game is lost, out of `max_lives`
+    // at the start of each game:
+    lives: u32,
+    max_lives: u32,
+    first_move: bool,
+    // Bombs come from a hand-made board & are never redrawn:
+    fixed: bool,
+    // Clock starts on the first open and stops when the game ends:
+    start: Option<Instant>,
+    stop: Option<Instant>,
+    // When the clock was paused, if it is. Resuming moves `start` forward by
+    // the time spent paused:
+    paused: Option<Instant>,
+    seed: u64,
+    rng: StdRng,
+    // Prior state of every cell changed by each recent action, oldest first:
+    history: VecDeque<Vec<(usize, usize, State)>>,
+}
+
+impl Field {
+    /// Creates a closed field. `mine_count`, when given, is the exact number
+    /// of bombs and overrides `bomb_pcnt`. Panics where `try_new` would fail:
+    pub fn new(
+        rows: usize,
+        cols: usize,
+        bomb_pcnt: usize,
+        mine_count: Option<usize>,
+        seed: u64,
+    ) -> Self {
+        Field::try_new(rows, cols, bomb_pcnt, mine_count, seed).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, but fails on dimensions rejected by `check_dimensions` or
+    /// more mines than fit alongside a safe first cell:
+    pub fn try_new(
+        rows: usize,
+        cols: usize,
+        bomb_pcnt: usize,
+        mine_count: Option<usize>,
+        seed: u64,
+    ) -> Result<Self, String> {
+        check_dimensions(rows, cols)?;
+        let cell_count = rows.checked_mul(cols).ok_or("The board is too large")?;
+        let bomb_pcnt = if bomb_pcnt > 100 { 100 } else { bomb_pcnt };
+        // Leave at least one cell free for the safe first open:
+        let bomb_count = match mine_count {
+            Some(count) if count >= cell_count => {
+                return Err(format!(
+                    "{} mines don't fit on a {}x{} board, which takes at most {}",
+                    count,
+                    rows,
+                    cols,
+                    cell_count - 1
+                ))
+            }
+            Some(count) => count,
+            None => cell_count
+                .checked_mul(bomb_pcnt)
+                .ok_or("The board is too large")?
+                .div_ceil(100)
+                .min(cell_count - 1),
+        };
+        let cells = vec![Cell::empty(); cell_count];
+
+        Ok(Self {
+            rows,
+            cols,
+            cells,
+            bomb_count,
+            unopened_safe_cells: rows * cols,
+            opened: 0,
+            flags: 0,
+            actions: 0,
+            action_pending: false,
+            three_bv: 0,
+            generation: 0,
+            cursor: [0, 0],
+            center_start: false,
+            wrap: false,
+            torus: false,
+            lives: 1,
+            max_lives: 1,
+            win_on_flags: false,
+            first_move: true,
+            fixed: false,
+            start: None,
+            stop: None,
+            paused: None,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            history: VecDeque::new(),
+        })
+    }
+
+    /// Creates a closed field with bombs exactly at `bombs`, kept as they are
+    /// through the first open & resets:
+    pub fn with_bombs(rows: usize, cols: usize, bombs: &[(usize, usize)], seed: u64) -> Self {
+        let mut field = Field::new(rows, cols, 0, None, seed);
+        for &(row, col) in bombs {
+            field.set_bomb_at(row, col);
+        }
+        field.bomb_count = field.cells.iter().filter(|cell| cell.pipebomb).count();
+        field.fixed = true;
+        field.update_adjacent();
+        field.count_states();
+        field
+    }
+
+    /// Adds or takes away the bomb under the cursor, to build a board by
+    /// hand. The board then keeps its bombs like any hand-made one:
+    pub fn toggle_bomb_at_cursor(&mut self) {
+        let [row, col] = self.cursor;
+        let cell = self.get_cell_mut(row, col);
+        cell.pipebomb = !cell.pipebomb;
+        if cell.pipebomb {
+            self.bomb_count += 1;
+        } else {
+            self.bomb_count -= 1;
+        }
+        self.fixed = true;
+        self.update_adjacent();
+        self.count_states();
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn bomb_count(&self) -> usize {
+        self.bomb_count
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// `[row, col]` of the cursor:
+    pub fn cursor(&self) -> [usize; 2] {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, row: usize, col: usize) {
+        self.cursor = [row.min(self.rows - 1), col.min(self.cols - 1)];
+    }
+
+    /// True until the bombs get placed by the first open:
+    pub fn is_first_move(&self) -> bool {
+        self.first_move
+    }
+
+    fn idx(&self, row: usize, col: usize) -> usize {
+        row * self.cols + col
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> &Cell {
+        &self.cells[self.idx(row, col)]
+    }
+
+    pub fn has_bomb_at(&self, row: usize, col: usize) -> bool {
+        self.cell(row, col).pipebomb
+    }
+
+    pub fn is_cursor_at(&self, row: usize, col: usize) -> bool {
+        row == self.cursor[0] && col == self.cursor[1]
+    }
+
+    fn set_bomb_at(&mut self, row: usize, col: usize) -> bool {
+        let has_bomb = self.has_bomb_at(row, col);
+        if !has_bomb {
+            self.get_cell_mut(row, col).pipebomb = true;
+            return true;
+        }
+        false
+    }
+
+    /// Resets the field. Bombs are placed later, on the first open, so that
+    /// the first opened cell is never a bomb:
+    pub fn randomize(&mut self) {
+        // Reset all cells:
+        for cell in self.cells.iter_mut() {
+            if self.fixed {
+                cell.state = State::Closed;
+            } else {
+                *cell = Cell::empty();
+            }
+        }
+        self.first_move = true;
+        self.start = None;
+        self.stop = None;
+        self.paused = None;
+        self.lives = self.max_lives;
+        self.actions = 0;
+        self.history.clear();
+        self.count_states();
+    }
+
+    /// Starts over on a different board: the next seed is drawn from the
+    /// current one's rng, so a run of new boards is still reproducible.
+    /// Hand-made boards keep their bombs:
+    pub fn new_board(&mut self) {
+        if !self.fixed {
+            self.seed = self.rng.gen();
+        }
+        self.randomize();
+    }
+
+    /// Closes every cell again & puts the cursor back where it started, but
+    /// keeps the bombs, to replay the same board. The clock restarts on the
+    /// next open:
+    pub fn reset_states(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.state = State::Closed;
+        }
+        self.cursor = self.start_cursor();
+        self.start = None;
+        self.stop = None;
+        self.paused = None;
+        self.lives = self.max_lives;
+        self.actions = 0;
+        self.history.clear();
+        self.count_states();
+    }
+
+    /// Randomizes the bombs from the stored seed, never placing one at
+    /// `(row, col)` or, when the board has room, around it, so the first open
+    /// floods. The same seed & first cell always produce the same layout:
+    pub fn place_bombs_excluding(&mut self, row: usize, col: usize) {
+        self.first_move = false;
+        self.start = Some(Instant::now());
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.draw_bombs(row, col);
+    }
+
+    /// Lays the bombs out now, from the seed & clear of `(row, col)`, then
+    /// keeps them through the first open & every reset, like a hand-made
+    /// board's. The same seed, size & cell always give the same board,
+    /// wherever the first open lands:
+    pub fn fix_bombs(&mut self, row: usize, col: usize) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.draw_bombs(row, col);
+        self.fixed = true;
+    }
+
+    /// Like `place_bombs_excluding`, but keeps redrawing (up to
+    /// `max_attempts` layouts) until the field can be cleared from `(row, col)`
+    /// without guessing. Returns false if it settled for the last layout:
+    pub fn place_bombs_no_guess(&mut self, row: usize, col: usize, max_attempts: usize) -> bool {
+        self.place_bombs_excluding(row, col);
+        for _ in 1..max_attempts {
+            if solver::is_solvable(self, row, col) {
+                return true;
+            }
+            self.draw_bombs(row, col);
+        }
+        solver::is_solvable(self, row, col)
+    }
+
+    /// Like `place_bombs_excluding`, but keeps redrawing (up to
+    /// `max_attempts` layouts) until opening `(row, col)` reveals at least
+    /// `percent`% of the safe cells. Returns false if it settled for the
+    /// layout with the biggest opening:
+    pub fn place_bombs_big_start(
+        &mut self,
+        row: usize,
+        col: usize,
+        percent: usize,
+        max_attempts: usize,
+    ) -> bool {
+        self.place_bombs_excluding(row, col);
+        let goal = (self.cells.len() - self.bomb_count) * percent.min(100);
+        let mut best = (self.opening_size(row, col), self.bomb_layout());
+        for _ in 1..max_attempts {
+            if best.0 * 100 >= goal {
+                break;
+            }
+            self.draw_bombs(row, col);
+            let size = self.opening_size(row, col);
+            if size > best.0 {
+                best = (size, self.bomb_layout());
+            }
+        }
+        for (cell, &pipebomb) in self.cells.iter_mut().zip(&best.1) {
+            cell.pipebomb = pipebomb;
+        }
+        self.update_adjacent();
+        self.count_states();
+        best.0 * 100 >= goal
+    }
+
+    fn bomb_layout(&self) -> Vec<bool> {
+        self.cells.iter().map(|cell| cell.pipebomb).collect()
+    }
+
+    /// How many cells opening the safe `(row, col)` would reveal on a closed
+    /// board, measured without opening anything:
+    fn opening_size(&self, row: usize, col: usize) -> usize {
+        let mut seen = vec![false; self.cells.len()];
+        seen[row * self.cols + col] = true;
+        let mut queue = vec![(row, col)];
+        let mut size = 0;
+        while let Some((row, col)) = queue.pop() {
+            size += 1;
+            if self.cell(row, col).adjacent > 0 {
+                continue;
+            }
+            for (r, c) in self.neighbors(row, col) {
+                if !seen[r * self.cols + c] && !self.has_bomb_at(r, c) {
+                    seen[r * self.cols + c] = true;
+                    queue.push((r, c));
+                }
+            }
+        }
+        size
+    }
+
+    /// Replaces any bombs with a fresh layout drawn from the rng:
+    fn draw_bombs(&mut self, row: usize, col: usize) {
+        if self.fixed {
+            return;
+        }
+        for cell in self.cells.iter_mut() {
+            cell.pipebomb = false;
+        }
+        // Keep the whole 3x3 around the first cell clear if the other cells
+        // can hold every bomb, or else just the cell itself:
+        let mut excluded = self.neighbors(row, col);
+        if self.bomb_count + excluded.len() >= self.cells.len() {
+            excluded.clear();
+        }
+        excluded.push((row, col));
+        for _ in 0..self.bomb_count {
+            // Keep drawing fresh spots until one is free & not excluded:
+            loop {
+                let r = self.rng.gen_range(0..self.rows);
+                let c = self.rng.gen_range(0..self.cols);
+                if !excluded.contains(&(r, c)) && self.set_bomb_at(r, c) {
+                    break;
+                }
+            }
+        }
+        self.update_adjacent();
+        self.count_states();
+    }
+
+    /// Recounts `unopened_safe_cells`, `opened` & `flags` from scratch, after
+    /// the bombs or the whole board changed:
+    fn count_states(&mut self) {
+        let (mut unopened_safe, mut opened, mut flags) = (0, 0, 0);
+        for cell in &self.cells {
+            match cell.state {
+                State::Open if !cell.pipebomb => opened += 1,
+                State::Open => (),
+                State::Flagged => flags += 1,
+                State::Closed | State::Questioned => (),
+            }
+            if !cell.pipebomb && cell.state != State::Open {
+                unopened_safe += 1;
+            }
+        }
+        self.unopened_safe_cells = unopened_safe;
+        self.opened = opened;
+        self.flags = flags;
+        self.generation += 1;
+    }
+
+    /// Changes a cell's state, keeping the counts in step. The first change
+    /// of an action counts the action:
+    fn write_state(&mut self, row: usize, col: usize, state: State) {
+        if self.action_pending {
+            self.action_pending = false;
+            self.actions += 1;
+        }
+        self.generation += 1;
+        let cell = self.get_cell_mut(row, col);
+        let old = cell.state;
+        let safe = !cell.pipebomb;
+        cell.state = state;
+        if safe && old != State::Open && state == State::Open {
+            self.unopened_safe_cells -= 1;
+            self.opened += 1;
+        } else if safe && old == State::Open && state != State::Open {
+            self.unopened_safe_cells += 1;
+            self.opened -= 1;
+        }
+        if old != State::Flagged && state == State::Flagged {
+            self.flags += 1;
+        } else if old == State::Flagged && state != State::Flagged {
+            self.flags -= 1;
+        }
+    }
+
+    /// Recomputes every cell's cached adjacent-bomb count, & the 3BV they
+    /// add up to:
+    fn update_adjacent(&mut self) {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                self.get_cell_mut(row, col).adjacent = self.neighbor_bomb_count(row, col);
+            }
+        }
+        self.three_bv = self.count_three_bv();
+    }
+
+    fn get_cell_mut(&mut self, row: usize, col: usize) -> &mut Cell {
+        let i = self.idx(row, col);
+        &mut self.cells[i]
+    }
+
+    /// Adjacent-bomb count of a cell, as cached on the last bomb placement:
+    pub fn bombs_around(&self, row: usize, col: usize) -> u32 {
+        self.cell(row, col).adjacent as u32
+    }
+
+    /// Bombs in the cells around `(row, col)`, scanning the same bounded
+    /// neighborhood as `neighbors`:
+    fn neighbor_bomb_count(&self, row: usize, col: usize) -> u8 {
+        self.neighbors(row, col)
+            .into_iter()
+            .filter(|&(r, c)| self.has_bomb_at(r, c))
+            .count() as u8
+    }
+
+    /// Starts recording a new undoable action:
+    fn begin_action(&mut self) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(Vec::new());
+        self.action_pending = true;
+    }
+
+    /// Finishes the current action, dropping it if it changed nothing:
+    fn end_action(&mut self) {
+        self.action_pending = false;
+        if self
+            .history
+            .back()
+            .is_some_and(|changes| changes.is_empty())
+        {
+            self.history.pop_back();
+        }
+    }
+
+    /// Changes a cell's state, remembering the old one for `undo`:
+    fn set_state(&mut self, row: usize, col: usize, state: State) {
+        let old = self.cell(row, col).state;
+        if old == state {
+            return;
+        }
+        if let Some(changes) = self.history.back_mut() {
+            changes.push((row, col, old));
+        }
+        self.write_state(row, col, state);
+    }
+
+    /// Reverts every cell changed by the last open/chord/flag action. Returns
+    /// false if there was nothing to undo:
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(changes) => {
+                for (row, col, state) in changes.into_iter().rev() {
+                    self.write_state(row, col, state);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Opens a safe cell. Bombs only ever show through `reveal_mines`:
+    fn open_at(&mut self, row: usize, col: usize) {
+        debug_assert!(!self.has_bomb_at(row, col), "opened a bomb at {row},{col}");
+        self.set_state(row, col, State::Open)
+    }
+
+    /// Opens the cell and flood fills its zero-adjacency region. Uses an
+    /// explicit work queue so large empty fields can't overflow the stack.
+    /// The fill stops at flags & question marks, only opening the latter when
+    /// it's the cell asked for:
+    pub fn check_at(&mut self, row: usize, col: usize) {
+        self.check_at_with(row, col, |_| ());
+    }
+
+    /// `check_at`, calling `on_ring` each time the fill has opened every cell
+    /// at one distance from `(row, col)` & is about to spread further, so a
+    /// front-end can show the opening grow:
+    pub fn check_at_with(&mut self, row: usize, col: usize, mut on_ring: impl FnMut(&Self)) {
+        let origin = (row, col);
+        let mut queue = VecDeque::new();
+        queue.push_back((origin, 0));
+        let mut ring = 0;
+
+        while let Some(((row, col), distance)) = queue.pop_front() {
+            if distance > ring {
+                ring = distance;
+                on_ring(self);
+            }
+            match self.cell(row, col).state {
+                State::Open | State::Flagged => continue,
+                State::Questioned if (row, col) != origin => continue,
+                State::Closed | State::Questioned => (),
+            }
+            if self.cell(row, col).pipebomb {
+                continue;
+            }
+            self.open_at(row, col);
+            if self.bombs_around(row, col) > 0 {
+                continue;
+            }
+
+            // All eight neighbors, each kept or dropped on its own row &
+            // column bounds. A zero has no bombs around it, but the fill
+            // never relies on that to skip them:
+            for (r, c) in self.neighbors(row, col) {
+                if !self.has_bomb_at(r, c) {
+                    queue.push_back(((r, c), distance + 1));
+                }
+            }
+        }
+    }
+
+    /// Opens the cell under the cursor, flagged or not (front-ends should
+    /// confirm before opening a flagged cell). Returns true if it was a bomb:
+    pub fn open_at_cursor(&mut self) -> bool {
+        self.open_at_cursor_with(|_| ())
+    }
+
+    /// `open_at_cursor`, with `on_ring` called as in `check_at_with`:
+    pub fn open_at_cursor_with(&mut self, on_ring: impl FnMut(&Self)) -> bool {
+        let row = self.cursor[0];
+        let col = self.cursor[1];
+        if self.cell(row, col).state == State::Open {
+            return false;
+        }
+        // A replayed board keeps its bombs, so its clock starts here instead:
+        self.start.get_or_insert_with(Instant::now);
+        self.begin_action();
+        if self.cell(row, col).state == State::Flagged {
+            self.set_state(row, col, State::Closed);
+        }
+        self.check_at_with(row, col, on_ring);
+        self.end_action();
+        self.cell(row, col).pipebomb
+    }
+
+    /// In-bounds coordinates of the up to 8 cells surrounding `(row, col)`,
+    /// reaching across the edges on a torus:
+    pub fn neighbors(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let mut neighbors = Vec::with_capacity(8);
+        for r in self.around(row, self.rows) {
+            for c in self.around(col, self.cols) {
+                if r != row || c != col {
+                    neighbors.push((r, c));
+                }
+            }
+        }
+        neighbors
+    }
+
+    /// Positions at most one step from `pos` along an axis `len` long. On a
+    /// torus the ends meet, & boards under 3 wide don't count a cell twice:
+    fn around(&self, pos: usize, len: usize) -> Vec<usize> {
+        if self.torus {
+            let mut around = vec![(pos + len - 1) % len, pos, (pos + 1) % len];
+            around.sort_unstable();
+            around.dedup();
+            around
+        } else {
+            (pos.saturating_sub(1)..=(pos + 1).min(len - 1)).collect()
+        }
+    }
+
+    /// Opens every unflagged neighbor of an open number whose flags already
+    /// match it. Returns true if one of them was a bomb:
+    pub fn chord_at_cursor(&mut self) -> bool {
+        let row = self.cursor[0];
+        let col = self.cursor[1];
+        if self.cell(row, col).state != State::Open || self.is_detonated(row, col) {
+            return false;
+        }
+
+        let neighbors = self.neighbors(row, col);
+        let flagged = neighbors
+            .iter()
+            .filter(|&&(r, c)| self.cell(r, c).state == State::Flagged || self.is_detonated(r, c))
+            .count() as u32;
+        if flagged != self.bombs_around(row, col) {
+            return false;
+        }
+
+        let mut hit_bomb = false;
+        self.begin_action();
+        for (r, c) in neighbors {
+            if matches!(self.cell(r, c).state, State::Closed | State::Questioned) {
+                hit_bomb |= self.cell(r, c).pipebomb;
+                self.check_at(r, c);
+            }
+        }
+        self.end_action();
+        hit_bomb
+    }
+
+    pub fn flag_at_cursor(&mut self) {
+        let [row, col] = self.cursor;
+        let next = match self.cell(row, col).state {
+            State::Closed => State::Flagged,
+            State::Flagged => State::Questioned,
+            State::Questioned => State::Closed,
+            State::Open => return,
+        };
+        self.begin_action();
+        self.set_state(row, col, next);
+        self.end_action();
+    }
+
+    /// Flags a closed or question-marked cell, as one undoable action. Unlike
+    /// `flag_at_cursor` it never cycles past the flag:
+    pub fn flag_at(&mut self, row: usize, col: usize) {
+        if matches!(self.cell(row, col).state, State::Closed | State::Questioned) {
+            self.begin_action();
+            self.set_state(row, col, State::Flagged);
+            self.end_action();
+        }
+    }
+
+    /// Flags the unopened neighbors of every open number that has exactly as
+    /// many unopened neighbors as bombs around it, since they must all be
+    /// bombs. Flags already in place are kept. Returns how many cells were
+    /// flagged, as one undoable action:
+    pub fn flag_obvious_mines(&mut self) -> usize {
+        self.begin_action();
+        let mut flagged = 0;
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let cell = self.cell(row, col);
+                if cell.state != State::Open || cell.pipebomb || cell.adjacent == 0 {
+                    continue;
+                }
+                let unopened: Vec<(usize, usize)> = self
+                    .neighbors(row, col)
+                    .into_iter()
+                    .filter(|&(r, c)| {
+                        self.cell(r, c).state != State::Open || self.is_detonated(r, c)
+                    })
+                    .collect();
+                if unopened.len() != self.bombs_around(row, col) as usize {
+                    continue;
+                }
+                for (r, c) in unopened {
+                    if matches!(self.cell(r, c).state, State::Closed | State::Questioned) {
+                        self.set_state(r, c, State::Flagged);
+                        flagged += 1;
+                    }
+                }
+            }
+        }
+        self.end_action();
+        flagged
+    }
+
+    /// Makes the cursor wrap around the board edges instead of stopping:
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    pub fn is_wrap(&self) -> bool {
+        self.wrap
+    }
+
+    /// Joins the opposite edges for adjacency, so every cell has 8
+    /// neighbors on boards at least 3x3:
+    pub fn set_torus(&mut self, torus: bool) {
+        self.torus = torus;
+        self.update_adjacent();
+        self.count_states();
+    }
+
+    pub fn is_torus(&self) -> bool {
+        self.torus
+    }
+
+    /// Lets each game survive `lives - 1` bombs going off:
+    pub fn set_lives(&mut self, lives: u32) {
+        self.max_lives = lives.max(1);
+        self.lives = self.max_lives;
+    }
+
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn max_lives(&self) -> u32 {
+        self.max_lives
+    }
+
+    /// Spends a life on the bombs the last open or chord at the cursor set
+    /// off, opening just those. Returns false, leaving them closed, if that
+    /// was the last life:
+    pub fn survive_explosion(&mut self) -> bool {
+        self.lives = self.lives.saturating_sub(1);
+        if self.lives == 0 {
+            return false;
+        }
+        let [row, col] = self.cursor;
+        let hit = if self.has_bomb_at(row, col) {
+            vec![(row, col)]
+        } else {
+            // A chord sets off every bomb it tried to open:
+            self.neighbors(row, col)
+                .into_iter()
+                .filter(|&(r, c)| {
+                    self.has_bomb_at(r, c)
+                        && matches!(self.cell(r, c).state, State::Closed | State::Questioned)
+                })
+                .collect()
+        };
+        // Opened outside the undo history, & dropped from it (a flag taken
+        // off to open it, say), so undoing can't win a life back:
+        for changes in self.history.iter_mut() {
+            changes.retain(|&(r, c, _)| !hit.contains(&(r, c)));
+        }
+        self.history.retain(|changes| !changes.is_empty());
+        for (r, c) in hit {
+            self.write_state(r, c, State::Open);
+        }
+        true
+    }
+
+    /// True for a bomb that went off & stayed open, with lives to spare:
+    pub fn is_detonated(&self, row: usize, col: usize) -> bool {
+        let cell = self.cell(row, col);
+        cell.pipebomb && cell.state == State::Open
+    }
+
+    /// Lets the game also be won by flagging every bomb & nothing else, as
+    /// well as by opening every safe cell:
+    pub fn set_win_on_flags(&mut self, win_on_flags: bool) {
+        self.win_on_flags = win_on_flags;
+    }
+
+    pub fn is_win_on_flags(&self) -> bool {
+        self.win_on_flags
+    }
+
+    /// Starts the cursor in the middle of the board instead of the top-left
+    /// corner, from now on & after every retry:
+    pub fn set_center_start(&mut self, center_start: bool) {
+        self.center_start = center_start;
+        self.cursor = self.start_cursor();
+    }
+
+    pub fn is_center_start(&self) -> bool {
+        self.center_start
+    }
+
+    /// Where the cursor starts a game:
+    fn start_cursor(&self) -> [usize; 2] {
+        if self.center_start {
+            [self.rows / 2, self.cols / 2]
+        } else {
+            [0, 0]
+        }
+    }
+
+    pub fn dec_cursor(&mut self, o: Orientation) {
+        let (pos, len) = match o {
+            Orientation::Vertical => (&mut self.cursor[0], self.rows),
+            Orientation::Horizontal => (&mut self.cursor[1], self.cols),
+        };
+        if *pos > 0 {
+            *pos -= 1;
+        } else if self.wrap {
+            *pos = len - 1;
+        }
+    }
+
+    pub fn inc_cursor(&mut self, o: Orientation) {
+        let (pos, len) = match o {
+            Orientation::Vertical => (&mut self.cursor[0], self.rows),
+            Orientation::Horizontal => (&mut self.cursor[1], self.cols),
+        };
+        if *pos < len - 1 {
+            *pos += 1;
+        } else if self.wrap {
+            *pos = 0;
+        }
+    }
+
+    pub fn reveal_mines(&mut self) {
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                if self.has_bomb_at(i, j) {
+                    self.write_state(i, j, State::Open);
+                }
+            }
+        }
+    }
+
+    /// Flags every bomb still hidden, as a won board is shown; bombs that
+    /// went off stay open:
+    pub fn flag_mines(&mut self) {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.has_bomb_at(row, col) && self.cell(row, col).state != State::Open {
+                    self.write_state(row, col, State::Flagged);
+                }
+            }
+        }
+    }
+
+    pub fn victory(&self) -> bool {
+        if self.unopened_safe_cells == 0 {
+            return true;
+        }
+        // Before the first open there are no bombs to flag:
+        self.win_on_flags
+            && !self.first_move
+            && self.cells.iter().all(|cell| match cell.state {
+                State::Flagged => cell.pipebomb,
+                // Bombs that went off count as found:
+                State::Open => true,
+                State::Closed | State::Questioned => !cell.pipebomb,
+            })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.start {
+            Some(start) => self.stop.or(self.paused).unwrap_or_else(Instant::now) - start,
+            None => Duration::ZERO,
+        }
+    }
+
+    /// True between the first open & the end of the game, unless paused:
+    pub fn clock_running(&self) -> bool {
+        self.start.is_some() && self.stop.is_none() && self.paused.is_none()
+    }
+
+    /// Freezes the clock until `resume_clock`, leaving the paused time out
+    /// of `elapsed`:
+    pub fn pause_clock(&mut self) {
+        if self.paused.is_none() {
+            self.paused = Some(Instant::now());
+        }
+    }
+
+    pub fn resume_clock(&mut self) {
+        if let (Some(paused), Some(start)) = (self.paused.take(), self.start.as_mut()) {
+            *start += paused.elapsed();
+        }
+    }
+
+    pub fn stop_clock(&mut self) {
+        if self.stop.is_none() {
+            self.stop = Some(Instant::now());
+        }
+    }
+
+    /// The grid as plain text, one line per row & three characters per cell:
+    /// `@` for a bomb, its count (or blank) for an open cell, `>` for a flag,
+    /// `?` for a question mark & `.` for a closed cell. The cursor's cell is
+    /// wrapped in `[ ]`:
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::with_capacity(self.rows * (self.cols * 3 + 1));
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let cell = self.cell(row, col);
+                let glyph = match cell.state {
+                    State::Open if cell.pipebomb => '@',
+                    State::Open if cell.adjacent > 0 => (b'0' + cell.adjacent) as char,
+                    State::Open => ' ',
+                    State::Closed => '.',
+                    State::Flagged => '>',
+                    State::Questioned => '?',
+                };
+                let (left, right) = if self.is_cursor_at(row, col) {
+                    ('[', ']')
+                } else {
+                    (' ', ' ')
+                };
+                text.push(left);
+                text.push(glyph);
+                text.push(right);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The board's 3BV: the fewest clicks that clear it, one per region of
+    /// blank cells (opening it opens its border too) plus one per number
+    /// outside every region's border. Only meaningful once bombs are placed:
+    pub fn three_bv(&self) -> usize {
+        self.three_bv
+    }
+
+    /// Counts the 3BV from the cached adjacent-bomb counts:
+    fn count_three_bv(&self) -> usize {
+        let mut reached = vec![false; self.cells.len()];
+        let mut clicks = 0;
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let cell = self.cell(row, col);
+                if cell.pipebomb || cell.adjacent > 0 || reached[self.idx(row, col)] {
+                    continue;
+                }
+                clicks += 1;
+                reached[self.idx(row, col)] = true;
+                let mut stack = vec![(row, col)];
+                while let Some((r, c)) = stack.pop() {
+                    for (nr, nc) in self.neighbors(r, c) {
+                        let i = self.idx(nr, nc);
+                        if reached[i] || self.cells[i].pipebomb {
+                            continue;
+                        }
+                        reached[i] = true;
+                        if self.cells[i].adjacent == 0 {
+                            stack.push((nr, nc));
+                        }
+                    }
+                }
+            }
+        }
+        clicks
+            + self
+                .cells
+                .iter()
+                .zip(&reached)
+                .filter(|(cell, &reached)| !cell.pipebomb && !reached)
+                .count()
+    }
+
+    /// Safe cells opened so far:
+    pub fn opened_count(&self) -> usize {
+        self.opened
+    }
+
+    pub fn flag_count(&self) -> usize {
+        self.flags
+    }
+
+    /// Changes whenever a cell does, or the bombs:
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Clicks spent so far this game: opens, chords & flag changes that
+    /// changed the board, however many cells each one opened:
+    pub fn actions(&self) -> usize {
+        self.actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flood_fill_opens_a_large_empty_field() {
+        let mut field = Field::with_bombs(100, 100, &[], 0);
+        field.check_at(0, 0);
+        assert!(field.cells.iter().all(|cell| cell.state == State::Open));
+        assert!(field.victory());
+    }
+
+    fn placed_bombs(field: &Field) -> usize {
+        field.cells.iter().filter(|cell| cell.pipebomb).count()
+    }
+
+    #[test]
+    fn rectangular_boards_get_their_share_of_bombs() {
+        // 16% of the cells, rounded up:
+        for (rows, cols, expected) in [(4, 20, 13), (20, 4, 13), (3, 50, 24), (1, 7, 2)] {
+            let mut field = Field::new(rows, cols, 16, None, 7);
+            assert_eq!(field.bomb_count(), expected, "{}x{}", rows, cols);
+            field.place_bombs_excluding(0, 0);
+            assert_eq!(placed_bombs(&field), expected, "{}x{}", rows, cols);
+        }
+    }
+
+    #[test]
+    fn placement_lays_exactly_the_bomb_count() {
+        // Dense enough that random draws keep colliding:
+        for seed in 0..50 {
+            let mut field = Field::new(10, 10, 0, Some(90), seed);
+            field.place_bombs_excluding(4, 4);
+            assert_eq!(placed_bombs(&field), 90, "seed {}", seed);
+            assert!(!field.has_bomb_at(4, 4));
+        }
+    }
+
+    /// Bombs around `(row, col)`, counted cell by cell without the cache:
+    fn brute_force_count(field: &Field, row: usize, col: usize) -> u8 {
+        let mut count = 0;
+        for r in row.saturating_sub(1)..=(row + 1).min(field.rows() - 1) {
+            for c in col.saturating_sub(1)..=(col + 1).min(field.cols() - 1) {
+                if (r, c) != (row, col) && field.has_bomb_at(r, c) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn cached_counts_match_a_brute_force_count() {
+        for seed in 0..20 {
+            let mut field = Field::new(12, 17, 25, None, seed);
+            field.place_bombs_excluding(5, 5);
+            for row in 0..field.rows() {
+                for col in 0..field.cols() {
+                    assert_eq!(
+                        field.cell(row, col).adjacent,
+                        brute_force_count(&field, row, col),
+                        "seed {} at {},{}",
+                        seed,
+                        row,
+                        col
+                    );
+                }
+            }
+        }
+    }
+
+    fn state_at(field: &Field, row: usize, col: usize) -> State {
+        field.cell(row, col).state
+    }
+
+    #[test]
+    fn flood_fill_stops_at_flags_and_question_marks() {
+        let mut field = Field::with_bombs(6, 6, &[], 0);
+        field.flag_at(2, 2);
+        field.set_cursor(3, 4);
+        field.flag_at_cursor();
+        field.flag_at_cursor();
+        field.check_at(0, 0);
+        assert_eq!(state_at(&field, 2, 2), State::Flagged);
+        assert_eq!(state_at(&field, 3, 4), State::Questioned);
+        assert_eq!(field.opened_count(), 34);
+    }
+
+    #[test]
+    fn extreme_sizes_are_rejected_before_allocating() {
+        for (rows, cols) in [
+            (100_000, 100_000),
+            (usize::MAX, usize::MAX),
+            (MAX_SIDE + 1, 1),
+            (1, MAX_SIDE + 1),
+            (0, 10),
+            (10, 0),
+        ] {
+            assert!(
+                Field::try_new(rows, cols, 16, None, 0).is_err(),
+                "{}x{}",
+                rows,
+                cols
+            );
+        }
+        assert!(Field::try_new(MAX_SIDE, MAX_SIDE, usize::MAX, None, 0).is_ok());
+        assert!(Field::try_new(10, 10, 0, Some(100), 0).is_err());
+        assert!(Field::try_new(10, 10, 0, Some(usize::MAX), 0).is_err());
+    }
+
+    #[test]
+    fn neighbor_counts_on_corners_edges_and_inside() {
+        // * . * .
+        // . . . .
+        // * * . *
+        let field = Field::with_bombs(3, 4, &[(0, 0), (0, 2), (2, 0), (2, 1), (2, 3)], 0);
+        for (row, col, expected) in [
+            // Corners:
+            (0, 0, 0),
+            (0, 3, 1),
+            (2, 3, 0),
+            // Edges:
+            (0, 1, 2),
+            (1, 0, 3),
+            (1, 3, 2),
+            // Inside:
+            (1, 1, 4),
+            (1, 2, 3),
+        ] {
+            assert_eq!(
+                field.neighbor_bomb_count(row, col),
+                expected,
+                "{},{}",
+                row,
+                col
+            );
+            assert_eq!(
+                field.bombs_around(row, col),
+                expected as u32,
+                "{},{}",
+                row,
+                col
+            );
+        }
+    }
+
+    /// Cells left open, as `(row, col)` in row-major order:
+    fn open_cells(field: &Field) -> Vec<(usize, usize)> {
+        (0..field.rows())
+            .flat_map(|row| (0..field.cols()).map(move |col| (row, col)))
+            .filter(|&(row, col)| state_at(field, row, col) == State::Open)
+            .collect()
+    }
+
+    #[test]
+    fn openings_from_corners_and_edges_reveal_their_region() {
+        // A wall of bombs down the middle column splits the board in two:
+        let wall: Vec<_> = (0..5).map(|row| (row, 2)).collect();
+        let left: Vec<_> = (0..5).flat_map(|row| [(row, 0), (row, 1)]).collect();
+        let right: Vec<_> = (0..5).flat_map(|row| [(row, 3), (row, 4)]).collect();
+        for (row, col, region) in [(0, 0, &left), (4, 4, &right), (2, 0, &left), (0, 4, &right)] {
+            let mut field = Field::with_bombs(5, 5, &wall, 0);
+            field.check_at(row, col);
+            assert_eq!(&open_cells(&field), region, "from {},{}", row, col);
+        }
+
+        // A corner number opens alone:
+        let mut field = Field::with_bombs(3, 3, &[(1, 1)], 0);
+        field.check_at(2, 2);
+        assert_eq!(open_cells(&field), [(2, 2)]);
+
+        // A bomb in one corner leaves the rest to a fill from the other,
+        // diagonals included:
+        let mut field = Field::with_bombs(4, 4, &[(0, 3)], 0);
+        field.check_at(3, 0);
+        assert_eq!(field.opened_count(), 15);
+        assert!(field.victory());
+    }
+
+    #[test]
+    fn safe_opens_never_open_a_bomb() {
+        let mut rng = StdRng::seed_from_u64(74);
+        for seed in 0..30 {
+            let mut field = Field::new(9, 11, 22, None, seed);
+            field.place_bombs_excluding(4, 5);
+            field.set_cursor(4, 5);
+            field.open_at_cursor();
+            while !field.victory() {
+                let row = rng.gen_range(0..field.rows());
+                let col = rng.gen_range(0..field.cols());
+                if field.has_bomb_at(row, col) {
+                    // Flags correctly, for chords to trust:
+                    field.flag_at(row, col);
+                    continue;
+                }
+                field.set_cursor(row, col);
+                if field.open_at_cursor() || field.chord_at_cursor() {
+                    panic!("seed {}: a safe move hit a bomb at {},{}", seed, row, col);
+                }
+                assert!(
+                    field
+                        .cells
+                        .iter()
+                        .all(|cell| !(cell.pipebomb && cell.state == State::Open)),
+                    "seed {}: a bomb was opened",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn first_open_floods_when_there_is_room() {
+        let mut rng = StdRng::seed_from_u64(75);
+        for seed in 0..50 {
+            let mut field = Field::new(9, 9, 0, Some(10), seed);
+            let (row, col) = (rng.gen_range(0..9), rng.gen_range(0..9));
+            field.place_bombs_excluding(row, col);
+            field.set_cursor(row, col);
+            assert!(!field.open_at_cursor());
+            assert_eq!(field.cell(row, col).adjacent, 0, "seed {}", seed);
+            assert!(field.opened_count() > 1, "seed {}", seed);
+        }
+
+        // Too dense to keep the 3x3 clear: only the cell itself is spared:
+        let mut field = Field::new(3, 3, 0, Some(8), 0);
+        field.place_bombs_excluding(1, 1);
+        field.set_cursor(1, 1);
+        assert!(!field.open_at_cursor());
+        assert_eq!(field.opened_count(), 1);
+    }
+
+    #[test]
+    fn full_density_leaves_one_safe_cell() {
+        for seed in 0..20 {
+            let mut field = Field::try_new(4, 5, 100, None, seed).unwrap();
+            assert_eq!(field.bomb_count(), 19);
+            field.place_bombs_excluding(2, 3);
+            assert_eq!(placed_bombs(&field), 19, "seed {}", seed);
+            field.set_cursor(2, 3);
+            assert!(!field.open_at_cursor());
+            assert!(field.victory());
+        }
+    }
+
+    #[test]
+    fn victory_comes_with_the_last_safe_cell() {
+        let mut field = Field::with_bombs(3, 3, &[(0, 0), (2, 2)], 0);
+        let safe: Vec<_> = (0..3)
+            .flat_map(|row| (0..3).map(move |col| (row, col)))
+            .filter(|&cell| cell != (0, 0) && cell != (2, 2))
+            .collect();
+        // Opening a corner's zero floods part of the board at once:
+        for (row, col) in safe {
+            field.set_cursor(row, col);
+            field.open_at_cursor();
+            assert_eq!(field.victory(), field.opened_count() == 7);
+        }
+        assert!(field.victory());
+        // Undoing the last open takes the win back:
+        field.undo();
+        assert!(!field.victory());
+    }
+
+    #[test]
+    fn flagging_exactly_the_bombs_wins_when_enabled() {
+        let mut field = Field::with_bombs(3, 3, &[(0, 0), (2, 2)], 0);
+        field.set_win_on_flags(true);
+        // No win by flags before the first open:
+        field.flag_at(0, 0);
+        field.flag_at(2, 2);
+        assert!(!field.victory());
+        field.undo();
+        field.undo();
+        field.place_bombs_excluding(1, 1);
+        field.set_cursor(1, 1);
+        field.open_at_cursor();
+        field.flag_at(0, 0);
+        assert!(!field.victory());
+        field.flag_at(2, 2);
+        assert!(field.victory());
+        // A flag on a safe cell spoils it:
+        field.flag_at(0, 1);
+        assert!(!field.victory());
+
+        let mut field = Field::with_bombs(3, 3, &[(0, 0), (2, 2)], 0);
+        field.place_bombs_excluding(1, 1);
+        field.set_cursor(1, 1);
+        field.open_at_cursor();
+        field.flag_at(0, 0);
+        field.flag_at(2, 2);
+        assert!(!field.victory());
+    }
+
+    #[test]
+    fn three_bv_is_counted_with_the_bombs() {
+        // One blank region, bordered by the 1 next to the bomb:
+        assert_eq!(Field::with_bombs(1, 3, &[(0, 0)], 0).three_bv(), 1);
+        // Eight numbers around the bomb & no blank cell to open them:
+        assert_eq!(Field::with_bombs(3, 3, &[(1, 1)], 0).three_bv(), 8);
+        // Each placement counts its own board:
+        for seed in 0..10 {
+            let mut field = Field::new(9, 9, 20, None, seed);
+            field.place_bombs_excluding(4, 4);
+            assert_eq!(field.three_bv(), field.count_three_bv(), "seed {}", seed);
+            field.place_bombs_big_start(4, 4, 50, 5);
+            assert_eq!(field.three_bv(), field.count_three_bv(), "seed {}", seed);
+            field.set_torus(true);
+            assert_eq!(field.three_bv(), field.count_three_bv(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn counts_follow_opens_flags_and_undo() {
+        let mut field = Field::with_bombs(3, 5, &[(0, 0)], 0);
+        field.place_bombs_excluding(2, 4);
+        field.set_cursor(2, 4);
+        field.open_at_cursor();
+        // The flood fill is one click, however far it spread:
+        assert_eq!((field.opened_count(), field.actions()), (14, 1));
+        field.set_cursor(0, 0);
+        field.flag_at_cursor();
+        assert_eq!((field.flag_count(), field.actions()), (1, 2));
+        // Flagging an open cell changes nothing, so isn't a click:
+        field.set_cursor(1, 1);
+        field.flag_at_cursor();
+        assert_eq!((field.flag_count(), field.actions()), (1, 2));
+        field.undo();
+        field.undo();
+        assert_eq!((field.opened_count(), field.flag_count()), (0, 0));
+        assert_eq!(field.actions(), 2);
+        field.reset_states();
+        assert_eq!(field.actions(), 0);
+    }
+
+    #[test]
+    fn the_generation_moves_with_the_cells_only() {
+        let mut field = Field::with_bombs(3, 5, &[(0, 0)], 0);
+        let generation = field.generation();
+        field.set_cursor(2, 4);
+        assert_eq!(field.generation(), generation);
+        field.open_at_cursor();
+        let opened = field.generation();
+        assert_ne!(opened, generation);
+        field.undo();
+        assert_ne!(field.generation(), opened);
+    }
+
+    #[test]
+    fn undo_never_hides_a_bomb_that_went_off() {
+        let mut field = Field::with_bombs(3, 5, &[(0, 0)], 0);
+        field.set_lives(2);
+        field.place_bombs_excluding(2, 4);
+        field.set_cursor(2, 4);
+        field.open_at_cursor();
+        // Flagged, then opened anyway:
+        field.set_cursor(0, 0);
+        field.flag_at_cursor();
+        assert!(field.open_at_cursor());
+        assert!(field.survive_explosion());
+        assert!(field.is_detonated(0, 0));
+        // Only the opening is left to undo:
+        assert!(field.undo());
+        assert!(field.is_detonated(0, 0));
+        assert_eq!((field.lives(), field.flag_count()), (1, 0));
+        assert!(!field.undo());
+    }
+}
@@ -1,482 +1,1947 @@
-use std::io::{self, Read, Write};
+mod args;
+mod command;
+mod config;
+mod daily;
+mod replay;
+mod scores;
+mod screen;
+mod stats;
+mod terminal;
+mod width;
 
-use libc;
-use rand::Rng;
-use termios::{tcsetattr, Termios, ECHO, ICANON, TCSANOW};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 
-macro_rules! clear_term {
-    () => {
-        // Clear screen and render field at the top
-        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-    };
-}
+use args::Difficulty;
+use command::{apply_command, Command, Direction, Outcome, Placement};
+use config::{CursorStyle, KeyMap, Palette, Theme};
+use pipebomb_sweeper::{check_dimensions, save, solver, Field, State};
+use rand::Rng;
+use replay::{Recorder, Replay};
+use scores::Scores;
+use screen::{text_line, Chunk, Line, Screen};
+use stats::Stats;
+use terminal::{read_byte, stdin_ready, take_resized, terminated};
 
 macro_rules! print_flush {
     ($($t:tt)*) => {
         {
-            write!(std::io::stdout(), $($t)*).unwrap();
+            print!($($t)*);
             std::io::stdout().flush().unwrap();
         }
     }
 }
 
-macro_rules! println_flush {
-    () => {
-        println!();
-        std::io::stdout().flush().unwrap();
+const ESC: u8 = 0x1b;
+const CTRL_C: char = '\x03';
+// Returned by `read_key` when a SIGTERM interrupted it:
+const TERMINATED: char = '\u{0}';
+// Stands in for a key when a `--countdown` runs out:
+const TIME_UP: char = '\u{1}';
+// How long to wait for the rest of an escape sequence before treating ESC as a
+// bare keypress:
+const ESC_TIMEOUT_MS: i32 = 50;
+// How often the clock is redrawn while it runs:
+const TICK: Duration = Duration::from_millis(200);
+
+// A countdown this close to running out is shown in the bomb color:
+const COUNTDOWN_WARNING: Duration = Duration::from_secs(10);
+const TIME_UP_NOTICE: &str = "Time's up!";
+// Longest wait between two commands of a replay, to skip over pauses:
+const REPLAY_MAX_GAP: Duration = Duration::from_secs(2);
+const STUCK_NOTICE: &str = "The solver is stuck: every move left is a guess.";
+// Pause between the auto-solver's steps, unless --animate sets one:
+const SOLVE_STEP: Duration = Duration::from_millis(150);
+const NO_FLAG_NOTICE: &str = "No flags in NF mode: open & chord only.";
+// Drawn across the middle of the hidden grid while paused:
+const PAUSED_BANNER: &str = "PAUSED";
+const PAUSED_NOTICE: &str = "Paused  z: resume  q: quit";
+const CONFIRM_REMINDER: &str = "Press Y or N, or Esc to cancel.";
+const TITLE_NOTICE: &str = "Up/Down: choose  Space/Enter: start  q: quit";
+const END_MENU: &str = "r: new board  R: retry  b: change difficulty  q: quit";
+const DIFFICULTY_MENU: &str = "New board: 1 Beginner  2 Intermediate  3 Expert  q: back";
+
+// Drawn over a flag that turned out wrong, whatever the theme:
+const MISFLAGGED: &str = "X";
+
+/// SGR codes for everything drawn in color:
+#[derive(Clone, Copy)]
+struct Colors<'a> {
+    // Indexed by adjacent-bomb count - 1:
+    numbers: [&'a str; 8],
+    pipebomb: &'a str,
+    flagged: &'a str,
+    hint: &'a str,
+    // Faint, so `--training` numbers don't pass for open cells:
+    training: &'a str,
+    // Reverse video sets the odds overlay apart from open numbers:
+    odds_low: &'a str,
+    odds_mid: &'a str,
+    odds_high: &'a str,
+    // Neither palette colors these:
+    closed: Option<&'a str>,
+    cursor: Option<&'a str>,
+}
+
+// The classic Windows palette:
+const CLASSIC_COLORS: Colors<'static> = Colors {
+    numbers: [
+        "94", // 1: blue
+        "32", // 2: green
+        "91", // 3: red
+        "34", // 4: dark blue
+        "31", // 5: maroon
+        "36", // 6: cyan
+        "30", // 7: black
+        "90", // 8: gray
+    ],
+    pipebomb: "1;31",
+    flagged: "33",
+    hint: "1;32",
+    training: "2",
+    odds_low: "7;32",
+    odds_mid: "7;33",
+    odds_high: "7;31",
+    closed: None,
+    cursor: None,
+};
+
+// Okabe-Ito hues from the 256-color palette, which stay apart with
+// deuteranopia & protanopia. Red & green never have to be told apart, & 5 to 8
+// are also underlined, so no number hangs on its hue alone:
+const COLORBLIND_COLORS: Colors<'static> = Colors {
+    numbers: [
+        "38;5;74",    // 1: sky blue
+        "38;5;214",   // 2: orange
+        "1;38;5;25",  // 3: bold blue
+        "1;38;5;166", // 4: bold vermilion
+        "4;38;5;175", // 5: underlined reddish purple
+        "4;38;5;36",  // 6: underlined bluish green
+        "4;1",        // 7: underlined bold
+        "4;38;5;245", // 8: underlined gray
+    ],
+    pipebomb: "1;38;5;166",
+    flagged: "38;5;221",
+    hint: "1;38;5;74",
+    training: "2",
+    odds_low: "7;38;5;74",
+    odds_mid: "7;38;5;221",
+    odds_high: "7;38;5;166",
+    closed: None,
+    cursor: None,
+};
+
+/// The theme's palette, with whatever its `[colors]` table overrides:
+fn colors(theme: &Theme) -> Colors<'_> {
+    let mut colors = match theme.palette {
+        Palette::Classic => CLASSIC_COLORS,
+        Palette::Colorblind => COLORBLIND_COLORS,
     };
-    ($($t:tt)*) => {
-        {
-            write!(std::io::stdout(), $($t)*).unwrap();
-            println!();
-            std::io::stdout().flush().unwrap();
-        }
+    let table = &theme.colors;
+    for (number, sgr) in colors.numbers.iter_mut().zip(&table.numbers) {
+        *number = sgr.as_deref().unwrap_or(number);
     }
+    colors.pipebomb = table.bomb.as_deref().unwrap_or(colors.pipebomb);
+    colors.flagged = table.flag.as_deref().unwrap_or(colors.flagged);
+    colors.closed = table.closed.as_deref();
+    colors.cursor = table.cursor.as_deref();
+    colors
 }
 
-const STDIN_FILENO: libc::c_int = 0;
-
-const PIPEBOMB: &str = "@";
-const FLAGGED: &str = ">";
-const CLOSED: &str = ".";
+// Columns taken by the row labels left of the grid:
+const LABEL_WIDTH: usize = 2;
+// How close the cursor gets to the viewport's edge before it scrolls:
+const SCROLL_MARGIN: usize = 2;
 
-#[derive(Clone, PartialEq)]
-enum State {
-    Open,
-    Closed,
-    Flagged,
+/// Wraps `glyph` in the given SGR color, unless colors are disabled:
+fn paint(glyph: String, sgr: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", sgr, glyph)
+    } else {
+        glyph
+    }
 }
 
-enum Orientation {
-    Vertical,
-    Horizontal,
+/// Rendering options & display-only game state:
+struct View {
+    // Name of the active difficulty preset or daily challenge, if any:
+    title: Option<String>,
+    // Best time on record for this board configuration:
+    best: Option<Duration>,
+    color: bool,
+    theme: Theme,
+    // Play time allowed by `--countdown`, which then shows the time left:
+    countdown: Option<Duration>,
+    // No-flag mode: flagging is off & the flag counts are hidden:
+    no_flag: bool,
+    // Set once a bomb goes off, to show which flags were wrong:
+    lost: bool,
+    // One-line message shown under the grid until the next key:
+    notice: Option<String>,
+    // Highlights the cursor until the next key, after a hint moved it:
+    hint: bool,
+    // Shows the numbers of closed safe cells, dimmed, as a learning aid:
+    training: bool,
+    // Draws every cell as if open until the next key, without touching the
+    // field, to check board generation or practice:
+    reveal_all: bool,
+    // Hides the grid behind a banner, so a paused game can't be studied:
+    paused: bool,
+    // Mine odds of the unopened cells, shown over the closed ones while on:
+    odds: Option<HashMap<(usize, usize), f64>>,
+    // The field generation the odds were worked out for:
+    odds_generation: Option<u64>,
+    viewport: Viewport,
+    tally: Tally,
+    // Statistics of the game that just ended, shown until the next one:
+    summary: Option<String>,
+    // The keys & modes in play, shown at the bottom unless `--no-help-bar`:
+    help_bar: Option<String>,
+    // Leaves the progress line out, for `--quiet`:
+    quiet: bool,
 }
 
-#[derive(Clone)]
-struct Cell {
-    state: State,
-    pipebomb: bool,
+/// Where the main loop is, from one game to the next:
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Playing,
+    /// The game is won or lost, its result shown with the end-of-game menu
+    /// until a key from it starts the next one:
+    GameOver,
+    /// Picking the difficulty of the next board, from the end-of-game menu:
+    Menu,
+    Quit,
 }
 
-impl Cell {
-    fn empty() -> Self {
-        Cell {
-            state: State::Closed,
-            pipebomb: false,
-        }
-    }
+/// Games won & lost since the program started, kept across resets:
+#[derive(Default)]
+struct Tally {
+    won: u32,
+    lost: u32,
 }
 
-struct Field {
+/// The part of the grid that fits in the terminal, as the rows & columns it
+/// starts at & how many of each are shown:
+#[derive(Default)]
+struct Viewport {
+    top: usize,
+    left: usize,
     rows: usize,
     cols: usize,
-    cells: Vec<Vec<Cell>>,
-    bomb_pcnt: usize,
-    cursor: [usize; 2],
+    // Set when the terminal can't fit a single cell next to everything
+    // around the grid:
+    cramped: bool,
+    // Columns of the terminal, to cut the help bar to:
+    width: usize,
 }
 
-impl Field {
-    fn new(rows: usize, cols: usize, bomb_pcnt: usize) -> Self {
-        let mut cells = Vec::new();
-        for i in 0..rows {
-            cells.push(vec![Cell::empty(); cols]);
-        }
-        let bomb_pcnt = if bomb_pcnt > 100 { 100 } else { bomb_pcnt };
+impl Viewport {
+    /// Fits the viewport to the terminal & scrolls it just enough to keep the
+    /// cursor `SCROLL_MARGIN` cells away from its edges. Everything around the
+    /// grid takes `chrome_rows` lines & each cell `cell_width` columns:
+    fn follow(&mut self, field: &Field, chrome_rows: usize, cell_width: usize) {
+        let (rows, cols) = match terminal::size() {
+            Some((term_rows, term_cols)) => {
+                self.width = term_cols;
+                (
+                    term_rows.saturating_sub(chrome_rows),
+                    term_cols.saturating_sub(LABEL_WIDTH + 2) / cell_width,
+                )
+            }
+            None => {
+                self.width = usize::MAX;
+                (field.rows(), field.cols())
+            }
+        };
+        self.cramped = rows == 0 || cols == 0;
+        let [row, col] = field.cursor();
+        (self.top, self.rows) = scroll(self.top, rows, field.rows(), row);
+        (self.left, self.cols) = scroll(self.left, cols, field.cols(), col);
+    }
+}
 
-        Self {
-            rows,
-            cols,
-            cells,
-            bomb_pcnt,
-            cursor: [0, 0],
-        }
+/// New origin & size along one axis of `len` cells, with room for `visible`,
+/// so that `pos` is shown:
+fn scroll(origin: usize, visible: usize, len: usize, pos: usize) -> (usize, usize) {
+    let visible = visible.clamp(1, len);
+    let margin = SCROLL_MARGIN.min((visible - 1) / 2);
+    let mut origin = origin.min(len - visible);
+    if pos < origin + margin {
+        origin = pos.saturating_sub(margin);
+    } else if pos + margin >= origin + visible {
+        origin = (pos + margin + 1 - visible).min(len - visible);
     }
+    (origin, visible)
+}
 
-    fn has_bomb_at(&self, row: usize, col: usize) -> bool {
-        self.cells[row][col].pipebomb
+/// Glyph for an open cell & its color: the bomb or its adjacent-bomb count:
+fn cell_str_at<'a>(
+    field: &Field,
+    row: usize,
+    col: usize,
+    theme: &'a Theme,
+) -> (String, Option<&'a str>) {
+    if field.has_bomb_at(row, col) {
+        return (theme.bomb.clone(), Some(colors(theme).pipebomb));
     }
 
-    fn is_cursor_at(&self, row: usize, col: usize) -> bool {
-        row == self.cursor[0] && col == self.cursor[1]
+    let bomb_count = field.cell(row, col).adjacent;
+    if bomb_count > 0 {
+        (
+            bomb_count.to_string(),
+            Some(colors(theme).numbers[bomb_count as usize - 1]),
+        )
+    } else {
+        (" ".to_owned(), None)
     }
+}
 
-    fn set_bomb_at(&mut self, row: usize, col: usize) -> bool {
-        let has_bomb = self.has_bomb_at(row, col);
-        if !has_bomb && !self.is_cursor_at(row, col) {
-            self.cells[row][col].pipebomb = true;
-            return true;
-        }
-        return false;
+/// Glyph for a closed cell in training mode: its number, dimmed, if it's
+/// safe & has one. Nothing is known before the bombs are placed:
+fn training_glyph<'a>(
+    field: &Field,
+    row: usize,
+    col: usize,
+    theme: &'a Theme,
+) -> (String, Option<&'a str>) {
+    let adjacent = field.cell(row, col).adjacent;
+    if field.is_first_move() || field.has_bomb_at(row, col) || adjacent == 0 {
+        (theme.closed.clone(), colors(theme).closed)
+    } else {
+        (adjacent.to_string(), Some(colors(theme).training))
     }
+}
 
-    /// Resets the field & randomizes it:
-    fn randomize(&mut self) {
-        // Reset all cells:
-        for i in 0..self.rows {
-            for j in 0..self.cols {
-                self.cells[i][j] = Cell::empty();
-            }
-        }
+/// Overlay glyph for a closed cell's mine odds: the chance in tenths,
+/// rounded down, shaded from green to red:
+fn odds_glyph(chance: f64, theme: &Theme) -> (String, Option<&str>) {
+    let tenths = (chance * 10.0).floor().clamp(0.0, 9.0) as u8;
+    let colors = colors(theme);
+    let sgr = match chance {
+        c if c < 0.2 => colors.odds_low,
+        c if c < 0.5 => colors.odds_mid,
+        _ => colors.odds_high,
+    };
+    (((b'0' + tenths) as char).to_string(), Some(sgr))
+}
 
-        let bomb_count = (self.rows * self.rows * self.bomb_pcnt + 99) / 100;
-        let mut rng = rand::thread_rng();
-        for i in 0..bomb_count {
-            let row = rng.gen_range(0..self.rows);
-            let col = rng.gen_range(0..self.cols);
+/// The overlay's legend, naming the safest cell:
+fn odds_notice(odds: &HashMap<(usize, usize), f64>) -> String {
+    let safest = odds
+        .iter()
+        .min_by(|a, b| a.1.total_cmp(b.1).then(a.0.cmp(b.0)));
+    match safest {
+        Some((&(row, col), chance)) => format!(
+            "Mine odds in tenths. Safest: row {} col {} ({:.0}%)",
+            row,
+            col,
+            chance * 100.0
+        ),
+        None => "Mine odds in tenths.".to_owned(),
+    }
+}
 
-            // Loop to avoid placing bombs on spots that already contain one:
-            while self.set_bomb_at(row, col) {}
-        }
+/// Single-character row/column label: the index in hex, wrapping after 'f':
+fn axis_label(i: usize) -> char {
+    char::from_digit((i % 16) as u32, 16).unwrap()
+}
+
+/// Lines `render` draws above the grid:
+fn header_rows(view: &View) -> usize {
+    view.title.is_some() as usize + !view.quiet as usize + 4
+}
+
+/// Lines `render` draws around the grid, plus one below for prompts:
+fn chrome_rows(view: &View) -> usize {
+    header_rows(view) + view.summary.is_some() as usize + view.help_bar.is_some() as usize + 3
+}
+
+/// The cell drawn at 1-based screen position `(row, col)`, reversing the
+/// layout `render` uses:
+fn cell_at(field: &Field, view: &View, row: usize, col: usize) -> Option<(usize, usize)> {
+    let grid_row = row.checked_sub(header_rows(view) + 1)?;
+    let grid_col = col.checked_sub(2 + LABEL_WIDTH)? / view.theme.cell_width();
+    let viewport = &view.viewport;
+    if viewport.cramped || grid_row >= viewport.rows || grid_col >= viewport.cols {
+        return None;
     }
+    let (row, col) = (viewport.top + grid_row, viewport.left + grid_col);
+    (row < field.rows() && col < field.cols()).then_some((row, col))
+}
 
-    //
-    fn cell_str_at(&self, row: usize, col: usize) -> String {
-        if self.has_bomb_at(row, col) {
-            return PIPEBOMB.to_owned();
+/// Lays out the header, grid & notice line. Every cell is its own chunk so
+/// `Screen` can redraw just the ones that changed:
+fn render(field: &Field, view: &View) -> Vec<Line> {
+    if view.viewport.cramped {
+        return vec![text_line(format!(
+            "Terminal too small: make it at least {} columns by {} rows",
+            LABEL_WIDTH + 2 + view.theme.cell_width(),
+            chrome_rows(view) + 1
+        ))];
+    }
+    let mut frame = Vec::new();
+    if let Some(title) = &view.title {
+        frame.push(text_line(title.clone()));
+    }
+    frame.push(text_line(format!("Seed: {}", field.seed())));
+    let flags = field.flag_count();
+    let clock = match time_left(field, view) {
+        // Rounded up, so the game ends as the count reaches 0:
+        Some(left) => {
+            let text = format!("Left: {}s", left.as_millis().div_ceil(1000));
+            if left < COUNTDOWN_WARNING {
+                paint(text, colors(&view.theme).pipebomb, view.color)
+            } else {
+                text
+            }
         }
-
-        let mut bomb_count = 0u8;
-        for i in -1..=1 {
-            for j in -1..=1 {
-                if i == 0 && j == 0 {
-                    continue;
+        None => format!("Time: {}s", field.elapsed().as_secs()),
+    };
+    let mut status = if view.no_flag {
+        format!("Mines: {}  {}", field.bomb_count(), clock)
+    } else {
+        format!(
+            "Mines: {}  Flags: {}  Remaining: {}  {}",
+            field.bomb_count(),
+            flags,
+            field.bomb_count() as isize - flags as isize,
+            clock
+        )
+    };
+    if field.max_lives() > 1 {
+        status.push_str(&format!("  Lives: {}/{}", field.lives(), field.max_lives()));
+    }
+    if let Some(best) = view.best {
+        status.push_str(&format!("  Best: {:.1}s", best.as_secs_f64()));
+    }
+    status.push_str(&format!("  W: {}  L: {}", view.tally.won, view.tally.lost));
+    frame.push(text_line(status));
+    // Progress & effort so far, in cells & in clicks, however many cells
+    // each click opened:
+    if !view.quiet {
+        let mut actions = format!(
+            "Opened: {}/{}  Clicks: {}",
+            field.opened_count(),
+            field.rows() * field.cols() - field.bomb_count(),
+            field.actions()
+        );
+        // The board's bombs aren't placed until the first open:
+        if !field.is_first_move() {
+            actions.push_str(&format!("  3BV: {}", field.three_bv()));
+        }
+        frame.push(text_line(actions));
+    }
+    // Only the viewport is drawn. Arrows in the borders show where more of
+    // the grid is hidden:
+    let Viewport {
+        top,
+        left,
+        rows,
+        cols,
+        ..
+    } = view.viewport;
+    // Cells are laid out as the cursor's brackets around the glyph, each
+    // padded to the widest the theme can draw there, so wide glyphs don't
+    // shift the columns:
+    let theme = &view.theme;
+    let cell_width = theme.cell_width();
+    let glyph_width = theme.glyph_width();
+    let space = " ".repeat(theme.bracket_width());
+    let margin = " ".repeat(LABEL_WIDTH);
+    let mut labels = String::new();
+    for c in left..left + cols {
+        labels.push_str(&format!(
+            "{}{}{}",
+            space,
+            width::pad(&axis_label(c).to_string(), glyph_width),
+            space
+        ));
+    }
+    let border = |more: bool, arrow: &str| {
+        let edge = if more { arrow } else { "_" };
+        let cell = format!("{}{}{}", space, width::pad(edge, glyph_width), space);
+        format!("{} {} ", margin, cell.repeat(cols))
+    };
+    let left_edge = if left > 0 { "<" } else { "|" };
+    let right_edge = if left + cols < field.cols() { ">" } else { "|" };
+    frame.push(text_line(format!("{} {} ", margin, labels)));
+    frame.push(text_line(border(top > 0, "^")));
+    for r in top..top + rows {
+        let mut line = vec![
+            Chunk {
+                col: 1,
+                text: axis_label(r).to_string(),
+            },
+            Chunk {
+                col: 1 + LABEL_WIDTH,
+                text: left_edge.to_owned(),
+            },
+        ];
+        if view.paused {
+            if r == top + rows / 2 {
+                let banner_col = (cell_width * cols).saturating_sub(PAUSED_BANNER.len()) / 2;
+                line.push(Chunk {
+                    col: 2 + LABEL_WIDTH + banner_col,
+                    text: PAUSED_BANNER.to_owned(),
+                });
+            }
+            line.push(Chunk {
+                col: 2 + LABEL_WIDTH + cell_width * cols,
+                text: right_edge.to_owned(),
+            });
+            frame.push(line);
+            continue;
+        }
+        for c in left..left + cols {
+            let cursor_here: bool = field.is_cursor_at(r, c);
+            let bracket = |text: &str| {
+                let text = width::pad(text, space.len());
+                match colors(theme).cursor {
+                    _ if view.hint => paint(text, colors(theme).hint, view.color),
+                    Some(sgr) => paint(text, sgr, view.color),
+                    None => text,
                 }
-                let r = row as isize + i;
-                let c = col as isize + j;
-
-                if r < 0 || r >= self.rows as isize || c < 0 || c >= self.cols as isize {
-                    continue;
+            };
+            let (open, close) = if cursor_here && theme.cursor_style() == CursorStyle::Brackets {
+                (bracket(&theme.cursor_left), bracket(&theme.cursor_right))
+            } else {
+                (space.clone(), space.clone())
+            };
+            // The odds overlay only covers cells still in play:
+            let chance = match &view.odds {
+                Some(odds) if !view.lost => odds.get(&(r, c)).copied(),
+                _ => None,
+            };
+            let (glyph, sgr) = match (field.cell(r, c).state, chance) {
+                _ if view.reveal_all => cell_str_at(field, r, c, theme),
+                (State::Open, _) => cell_str_at(field, r, c, theme),
+                (State::Closed | State::Questioned, Some(chance)) => odds_glyph(chance, theme),
+                (State::Closed, _) if view.training => training_glyph(field, r, c, theme),
+                (State::Closed, _) => (theme.closed.clone(), colors(theme).closed),
+                (State::Flagged, _) if view.lost && !field.has_bomb_at(r, c) => {
+                    (MISFLAGGED.to_owned(), Some(colors(theme).pipebomb))
                 }
-
-                // If not out of bounds, use them to index sorrounding cells:
-                if self.has_bomb_at(r as usize, c as usize) {
-                    bomb_count += 1;
+                (State::Flagged, _) => (theme.flag.clone(), Some(colors(theme).flagged)),
+                (State::Questioned, _) => (theme.question.clone(), None),
+            };
+            let glyph = width::pad(&glyph, glyph_width);
+            let glyph = match sgr {
+                Some(sgr) => paint(glyph, sgr, view.color),
+                None => glyph,
+            };
+            // Neither is a color, so `--no-color` keeps them, without the
+            // cursor's own color:
+            let cursor_sgr = match theme.cursor_style() {
+                CursorStyle::Reverse if cursor_here => Some("7"),
+                CursorStyle::Bold if cursor_here => Some("1"),
+                _ => None,
+            };
+            let glyph = match (cursor_sgr, colors(theme).cursor) {
+                (Some(style), Some(sgr)) if view.color => {
+                    format!("\x1b[{};{}m{}\x1b[0m", style, sgr, glyph)
                 }
-            }
+                (Some(style), _) => format!("\x1b[{}m{}\x1b[0m", style, glyph),
+                (None, _) => glyph,
+            };
+            line.push(Chunk {
+                col: 2 + LABEL_WIDTH + cell_width * (c - left),
+                text: format!("{}{}{}", open, glyph, close),
+            });
         }
-        return if bomb_count > 0 {
-            bomb_count.to_string()
-        } else {
-            " ".to_owned()
-        };
+        line.push(Chunk {
+            col: 2 + LABEL_WIDTH + cell_width * cols,
+            text: right_edge.to_owned(),
+        });
+        frame.push(line);
+    }
+    frame.push(text_line(border(top + rows < field.rows(), "v")));
+    if let Some(summary) = &view.summary {
+        frame.push(text_line(summary.clone()));
     }
+    frame.push(text_line(view.notice.as_deref().unwrap_or("").to_owned()));
+    // A wrapped line would push the frame past the bottom of the terminal:
+    if let Some(help_bar) = &view.help_bar {
+        let width = view.viewport.width.saturating_sub(1);
+        frame.push(text_line(width::truncate(help_bar, width).to_owned()));
+    }
+    frame
+}
 
-    fn get_cell_mut(&mut self, row: usize, col: usize) -> &mut Cell {
-        &mut self.cells[row][col]
+/// Keys that still work on a lost board, to move around & open the cells
+/// that were left, seeing what they held:
+fn is_review_key(key: char) -> bool {
+    "AaHhWwKksJjDdLl0$gG: ".contains(key)
+}
+
+/// One line of statistics for a game that just ended. Flags are counted
+/// before `reveal_mines` opens the ones on bombs. A win also gets its
+/// efficiency, the 3BV per click:
+fn summary(field: &Field, view: &View) -> String {
+    let mut summary = format!(
+        "Time: {:.1}s  Opened: {}/{}  Flags: {}  3BV: {}  Clicks: {}",
+        field.elapsed().as_secs_f64(),
+        field.opened_count(),
+        field.rows() * field.cols() - field.bomb_count(),
+        field.flag_count(),
+        field.three_bv(),
+        field.actions()
+    );
+    if !view.lost && field.actions() > 0 {
+        summary.push_str(&format!(
+            "  Efficiency: {}%",
+            field.three_bv() * 100 / field.actions()
+        ));
     }
+    summary
+}
 
-    fn out_of_bounds(&self, irow: isize, icol: isize) -> (bool, bool) {
-        (
-            irow < 0 || irow >= self.rows as isize,
-            icol < 0 || icol >= self.cols as isize,
-        )
+/// Play time left before a `--countdown` game is lost:
+fn time_left(field: &Field, view: &View) -> Option<Duration> {
+    view.countdown
+        .map(|countdown| countdown.saturating_sub(field.elapsed()))
+}
+
+fn time_up(field: &Field, view: &View) -> bool {
+    time_left(field, view) == Some(Duration::ZERO)
+}
+
+/// The help bar's line: the keys, as bound, & any modes that change the
+/// game from a plain one:
+fn help_bar(args: &args::Args, keys: &KeyMap, difficulty: Option<Difficulty>) -> String {
+    let mut modes = Vec::new();
+    if args.daily {
+        modes.push("Daily".to_owned());
+    } else if let Some(difficulty) = difficulty {
+        modes.push(difficulty.name().to_owned());
+    }
+    for (on, mode) in [
+        (args.no_flag, "NF"),
+        (args.no_guess, "No guess"),
+        (args.big_start.is_some(), "Big start"),
+        (args.torus, "Torus"),
+        (args.win_on_flags, "Win on flags"),
+        (args.training, "Training"),
+    ] {
+        if on {
+            modes.push(mode.to_owned());
+        }
+    }
+    if args.lives > 1 {
+        modes.push(format!("{} lives", args.lives));
+    }
+    if let Some(seconds) = args.countdown {
+        modes.push(format!("{}s countdown", seconds));
+    }
+    if modes.is_empty() {
+        keys.help()
+    } else {
+        format!("{}  |  {}", keys.help(), modes.join(", "))
     }
+}
 
-    fn bombs_around(&self, irow: isize, icol: isize) -> u32 {
-        let mut bomb_count = 0u32;
-        for i in -1..=1 {
-            for j in -1..=1 {
-                if i == 0 && j == 0 {
-                    continue;
-                }
-                let r = irow as isize + i;
-                let c = icol as isize + j;
+/// The line above the board: the preset or daily challenge being played, if
+/// any, & the variants that change the game:
+fn board_title(mode: Option<String>, args: &args::Args) -> Option<String> {
+    let mut title = mode;
+    for (on, variant) in [(args.no_flag, "NF mode"), (args.torus, "Torus")] {
+        if on {
+            title = Some(match title {
+                Some(title) => format!("{}  {}", title, variant),
+                None => variant.to_owned(),
+            });
+        }
+    }
+    title
+}
 
-                if r < 0 || r >= self.rows as isize || c < 0 || c >= self.cols as isize {
-                    continue;
-                }
+/// A fresh board of `difficulty`, playing by the same rules as `field`:
+fn board_like(field: &Field, difficulty: Difficulty) -> Field {
+    let (rows, cols, mines) = difficulty.dimensions();
+    let mut board = Field::new(rows, cols, 0, Some(mines), rand::thread_rng().gen());
+    board.set_wrap(field.is_wrap());
+    board.set_torus(field.is_torus());
+    board.set_lives(field.max_lives());
+    board.set_win_on_flags(field.is_win_on_flags());
+    board.set_center_start(field.is_center_start());
+    board.randomize();
+    board
+}
+
+/// The boards the title screen offers, `None` being a custom one:
+const TITLE_CHOICES: [Option<Difficulty>; 4] = [
+    Some(Difficulty::Beginner),
+    Some(Difficulty::Intermediate),
+    Some(Difficulty::Expert),
+    None,
+];
+
+/// Asks for the size & density of a custom board, each defaulting to what
+/// `args` already has. Returns `(rows, cols, density)`, or a notice if an
+/// answer is cancelled or invalid:
+fn custom_board(args: &args::Args, buffer: &mut [u8]) -> Result<(usize, usize, usize), String> {
+    let mut ask = |name: &str, default: usize| {
+        let text = read_line(buffer, &format!("{} ({}): ", name, default))
+            .ok_or_else(|| TITLE_NOTICE.to_owned())?;
+        match text.trim() {
+            "" => Ok(default),
+            text => text
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid {}: '{}'", name.to_lowercase(), text)),
+        }
+    };
+    let rows = ask("Rows", args.rows)?;
+    let cols = ask("Columns", args.cols)?;
+    check_dimensions(rows, cols)?;
+    let density = ask("Density (%)", args.density)?;
+    if density > 100 {
+        return Err(format!("Invalid density: {}%", density));
+    }
+    Ok((rows, cols, density))
+}
 
-                // If not out of bounds, use them to index sorrounding cells:
-                if self.has_bomb_at(r as usize, c as usize) {
-                    bomb_count += 1;
+/// Lets the player pick the board when the game is run without arguments,
+/// setting it in `args`. Returns false if they quit instead:
+fn run_title_screen(args: &mut args::Args, keys: &KeyMap) -> bool {
+    let mut buffer = [0u8; 1];
+    let mut screen = Screen::new();
+    let mut selected = 0;
+    let mut notice = TITLE_NOTICE.to_owned();
+    loop {
+        let mut frame = vec![text_line("pipebomb-sweeper".to_owned()), Vec::new()];
+        for (i, choice) in TITLE_CHOICES.iter().enumerate() {
+            let marker = if i == selected { '>' } else { ' ' };
+            frame.push(text_line(match choice {
+                Some(difficulty) => {
+                    let (rows, cols, mines) = difficulty.dimensions();
+                    format!(
+                        "{} {:<14}{}x{}, {} mines",
+                        marker,
+                        difficulty.name(),
+                        rows,
+                        cols,
+                        mines
+                    )
                 }
+                None => format!("{} {:<14}Your own size & density", marker, "Custom"),
+            }));
+        }
+        frame.push(Vec::new());
+        frame.push(text_line(notice.clone()));
+        screen.draw(frame);
+        let key = match read_input(&mut buffer, None, keys) {
+            Input::Key(key) => key,
+            Input::Resize => {
+                screen.invalidate();
+                continue;
+            }
+            _ => continue,
+        };
+        match key {
+            'W' | 'w' | 'K' | 'k' => selected = selected.saturating_sub(1),
+            'S' | 's' | 'J' | 'j' => selected = (selected + 1).min(TITLE_CHOICES.len() - 1),
+            '1'..='4' => selected = key as usize - '1' as usize,
+            'Q' | 'q' | CTRL_C | TERMINATED => return false,
+            _ => (),
+        }
+        if !matches!(key, ' ' | '\r' | '\n' | '1'..='4') {
+            continue;
+        }
+        match TITLE_CHOICES[selected] {
+            Some(difficulty) => {
+                let (rows, cols, mines) = difficulty.dimensions();
+                args.difficulty = Some(difficulty);
+                args.rows = rows;
+                args.cols = cols;
+                args.mines = Some(mines);
+                return true;
             }
+            None => match custom_board(args, &mut buffer) {
+                Ok((rows, cols, density)) => {
+                    args.rows = rows;
+                    args.cols = cols;
+                    args.density = density;
+                    return true;
+                }
+                Err(message) => {
+                    if terminated() {
+                        return false;
+                    }
+                    notice = message;
+                    // The prompt was printed below the frame:
+                    screen.invalidate();
+                }
+            },
         }
-        return bomb_count;
     }
+}
+
+fn enable_raw_mode() -> terminal::RawMode {
+    terminal::RawMode::enable().unwrap_or_else(|e| {
+        eprintln!("Can't set up the terminal: {}", e);
+        process::exit(1);
+    })
+}
 
-    fn open_at(&mut self, row: usize, col: usize) {
-        self.cells[row][col].state = State::Open
+/// Shown when a bomb goes off but the game goes on:
+fn lives_notice(field: &Field) -> String {
+    match field.lives() {
+        1 => "Boom! That cost a life, 1 left.".to_owned(),
+        lives => format!("Boom! That cost a life, {} left.", lives),
     }
+}
 
-    fn check_at(&mut self, row: usize, col: usize) {
-        if self.cells[row][col].pipebomb {
-            return;
+/// Asks a yes/no question below the grid, or takes a yes without asking
+/// when `ask` is off (`--no-confirm`). Esc, Ctrl-C & a SIGTERM count as no.
+/// Any other key, arrows included, only gets a reminder of the answers:
+fn confirm(ask: bool, buffer: &mut [u8], prompt: &str) -> bool {
+    if !ask {
+        return true;
+    }
+    print_flush!("\n{}", prompt);
+    let mut reminded = false;
+    loop {
+        match read_byte(buffer) {
+            Err(_) if terminated() => return false,
+            result => result.unwrap(),
         }
-        if self.bombs_around(row as isize, col as isize) > 0 {
-            self.open_at(row, col);
-            return;
+        let byte = buffer[0];
+        let lone_escape = byte == ESC && !stdin_ready(ESC_TIMEOUT_MS);
+        if let Some(answer) = answer(byte, lone_escape) {
+            return answer;
         }
-
-        match self.cells[row][col].state {
-            State::Open => return,
-            State::Closed => self.open_at(row, col),
-            _ => (),
+        if byte == ESC {
+            skip_escape_sequence(buffer);
         }
+        if !reminded {
+            print_flush!("\n{}", CONFIRM_REMINDER);
+            reminded = true;
+        }
+    }
+}
 
-        let positive_oob = self.out_of_bounds(row as isize + 1, col as isize + 1);
-        let negative_oob = self.out_of_bounds(row as isize - 1, col as isize - 1);
+/// What a key says to a yes/no question, if anything. `lone_escape` tells a
+/// bare Esc from the start of an escape sequence:
+fn answer(byte: u8, lone_escape: bool) -> Option<bool> {
+    match byte {
+        b'Y' | b'y' => Some(true),
+        b'N' | b'n' | 0x03 => Some(false),
+        ESC if lone_escape => Some(false),
+        _ => None,
+    }
+}
 
-        // Up
-        if !negative_oob.0 {
-            self.check_at(row - 1, col);
+/// Reads the rest of an escape sequence, like an arrow key's or a mouse
+/// report's, so none of it passes for keys of its own:
+fn skip_escape_sequence(buffer: &mut [u8]) {
+    while stdin_ready(ESC_TIMEOUT_MS) {
+        read_byte(buffer).unwrap();
+        // The final byte, past the `[` & any parameters:
+        if (0x40..=0x7e).contains(&buffer[0]) && buffer[0] != b'[' {
+            return;
         }
+    }
+}
 
-        // Left
-        if !negative_oob.1 {
-            self.check_at(row, col - 1);
-        }
+enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
 
-        // Down
-        if !positive_oob.0 {
-            self.check_at(row + 1, col);
-        }
+/// A key, or a mouse button pressed at a 1-based `(row, col)` of the screen:
+enum Input {
+    Key(char),
+    Click(MouseButton, usize, usize),
+    // The terminal changed size:
+    Resize,
+    // Nothing happened for a while, but the clock may need redrawing:
+    Tick,
+}
 
-        // Right
-        if !positive_oob.1 {
-            self.check_at(row, col + 1);
+/// Reads the rest of an SGR mouse report, `ESC [ < button ; col ; row M`,
+/// with `m` instead of `M` for a release. Only presses of the three main
+/// buttons are reported:
+fn read_mouse(buffer: &mut [u8]) -> Option<Input> {
+    let mut report = String::new();
+    loop {
+        if !stdin_ready(ESC_TIMEOUT_MS) {
+            return None;
         }
-
-        // Diag UL
-        if !negative_oob.0 && !negative_oob.1 {
-            self.check_at(row - 1, col - 1);
+        read_byte(buffer).unwrap();
+        match buffer[0] {
+            b'M' => break,
+            b'm' => return None,
+            byte => report.push(byte as char),
         }
+    }
+    let mut fields = report.split(';').map(|field| field.parse::<usize>().ok());
+    let (button, col, row) = (fields.next()??, fields.next()??, fields.next()??);
+    let button = match button {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        _ => return None,
+    };
+    Some(Input::Click(button, row, col))
+}
 
-        // Diag DL
-        if !positive_oob.0 && !negative_oob.1 {
-            self.check_at(row + 1, col - 1);
+/// Reads one key or click, or reports a resize, or a tick once `tick` has
+/// gone by without either. Arrow keys (`ESC [ A/B/C/D`) are translated to
+/// their WASD equivalents, Home/End to '0'/'$', Page Up/Down to 'g'/'G' and
+/// a bare Escape to 'q'. Only typed keys go through `keys`, so the arrows
+/// keep moving whatever the letters are bound to:
+fn read_input(buffer: &mut [u8], tick: Option<Duration>, keys: &KeyMap) -> Input {
+    let deadline = tick.map(|tick| Instant::now() + tick);
+    // A signal wakes up the wait, to redraw for a resize or quit on SIGTERM:
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) => left.as_millis() as i32,
+                None => return Input::Tick,
+            },
+            None => -1,
+        };
+        if stdin_ready(timeout) {
+            break;
         }
-
-        // Diag UR
-        if !negative_oob.0 && !positive_oob.1 {
-            self.check_at(row - 1, col + 1);
+        if terminated() {
+            return Input::Key(TERMINATED);
         }
-
-        // Diag DR
-        if !positive_oob.0 && !positive_oob.1 {
-            self.check_at(row + 1, col + 1);
+        if take_resized() {
+            return Input::Resize;
         }
     }
+    match read_byte(buffer) {
+        Err(_) if terminated() => return Input::Key(TERMINATED),
+        result => result.unwrap(),
+    }
+    if buffer[0] != ESC {
+        return Input::Key(keys.translate(buffer[0] as char));
+    }
+    if !stdin_ready(ESC_TIMEOUT_MS) {
+        return Input::Key('q');
+    }
+    read_byte(buffer).unwrap();
+    if buffer[0] != b'[' || !stdin_ready(ESC_TIMEOUT_MS) {
+        return Input::Key('\0');
+    }
+    read_byte(buffer).unwrap();
+    if buffer[0] == b'<' {
+        return read_mouse(buffer).unwrap_or(Input::Key('\0'));
+    }
+    Input::Key(match buffer[0] {
+        b'A' => 'w',
+        b'B' => 's',
+        b'C' => 'd',
+        b'D' => 'a',
+        b'H' => '0',
+        b'F' => '$',
+        // `ESC [ n ~` sequences:
+        b'1'..=b'8' => {
+            let n = buffer[0];
+            if !stdin_ready(ESC_TIMEOUT_MS) {
+                return Input::Key('\0');
+            }
+            read_byte(buffer).unwrap();
+            if buffer[0] != b'~' {
+                return Input::Key('\0');
+            }
+            match n {
+                b'1' | b'7' => '0',
+                b'4' | b'8' => '$',
+                b'5' => 'g',
+                b'6' => 'G',
+                _ => '\0',
+            }
+        }
+        _ => '\0',
+    })
+}
 
-    // TODO: Open recursively empty spaces
-    fn open_at_cursor(&mut self, buffer: &mut [u8]) -> bool {
-        let row = self.cursor[0];
-        let col = self.cursor[1];
-        match self.cells[row][col].state {
-            State::Closed => self.check_at(row, col),
-            State::Flagged => {
-                print_flush!("\nAre you sure you want to open this flagged cell? (Y/N): ");
-                loop {
-                    std::io::stdin().read_exact(buffer).unwrap();
-                    match buffer[0] as char {
-                        'Y' | 'y' => {
-                            // cell.state = State::Open;
-                            self.check_at(row, col);
-                            break;
-                        }
-                        'N' | 'n' => {
-                            break;
-                        }
-                        _ => (),
-                    }
-                }
+/// Prompts for a line of text, echoing it since the terminal doesn't.
+/// Backspace edits, Enter accepts and Esc or Ctrl-C cancels:
+fn read_line(buffer: &mut [u8], prompt: &str) -> Option<String> {
+    print_flush!("\n{}", prompt);
+    let mut line = String::new();
+    loop {
+        match read_byte(buffer) {
+            Err(_) if terminated() => return None,
+            result => result.unwrap(),
+        }
+        match buffer[0] {
+            b'\r' | b'\n' => return Some(line),
+            ESC | 0x03 => return None,
+            // Backspace/Delete:
+            0x08 | 0x7f if line.pop().is_some() => print_flush!("\x08 \x08"),
+            byte if byte.is_ascii_graphic() || byte == b' ' => {
+                line.push(byte as char);
+                print_flush!("{}", byte as char);
             }
             _ => (),
         }
-        self.cells[row][col].pipebomb
     }
+}
 
-    fn flag_at_cursor(&mut self) {
-        let mut cell: &mut Cell = self.get_cell_mut(self.cursor[0], self.cursor[1]);
-        match cell.state {
-            State::Closed => cell.state = State::Flagged,
-            State::Flagged => cell.state = State::Closed,
-            _ => (),
-        }
+/// Parses `row col` (space or comma separated) into a cell of `field`:
+fn parse_coordinate(field: &Field, text: &str) -> Option<(usize, usize)> {
+    let mut parts = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty());
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || row >= field.rows() || col >= field.cols() {
+        return None;
+    }
+    Some((row, col))
+}
+
+/// Default location for 'S':
+fn save_path() -> Option<PathBuf> {
+    scores::data_dir().map(|dir| dir.join("saved-game.txt"))
+}
+
+/// Written when the game is killed by SIGTERM, read by `--resume`:
+fn autosave_path() -> Option<PathBuf> {
+    scores::data_dir().map(|dir| dir.join("autosave.txt"))
+}
+
+fn save_board(field: &Field, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
     }
+    fs::write(path, save::board_text(field))
+}
+
+const EDIT_NOTICE: &str = "Space/f: add or remove a mine  S: save  q: quit";
 
-    fn dec_cursor(&mut self, o: Orientation) {
-        match o {
-            Orientation::Vertical => {
-                if self.cursor[0] > 0 {
-                    self.cursor[0] -= 1;
+/// The board editor: every cell is drawn open, so the mines & the numbers
+/// around them show as they're placed. Saves to `path` on 'S':
+fn run_editor(field: &mut Field, view: &mut View, path: &Path, keys: &KeyMap, confirm_quit: bool) {
+    let mut buffer = [0u8; 1];
+    let mut screen = Screen::new();
+    view.reveal_all = true;
+    view.notice = Some(EDIT_NOTICE.to_owned());
+    // The game's keys & modes don't apply here:
+    view.help_bar = None;
+    let mut saved = true;
+    loop {
+        view.viewport
+            .follow(field, chrome_rows(view), view.theme.cell_width());
+        screen.draw(render(field, view));
+        let key = match read_input(&mut buffer, None, keys) {
+            Input::Key(key) => key,
+            Input::Click(_, row, col) => match cell_at(field, view, row, col) {
+                Some((row, col)) => {
+                    field.set_cursor(row, col);
+                    ' '
                 }
+                None => continue,
+            },
+            Input::Resize => {
+                screen.invalidate();
+                continue;
             }
-            Orientation::Horizontal => {
-                if self.cursor[1] > 0 {
-                    self.cursor[1] -= 1;
-                }
+            Input::Tick => continue,
+        };
+        view.notice = Some(EDIT_NOTICE.to_owned());
+        let direction = match key {
+            'A' | 'a' | 'H' | 'h' => Some(Command::Move(Direction::Left)),
+            'W' | 'w' | 'K' | 'k' => Some(Command::Move(Direction::Up)),
+            's' | 'J' | 'j' => Some(Command::Move(Direction::Down)),
+            'D' | 'd' | 'L' | 'l' => Some(Command::Move(Direction::Right)),
+            '0' => Some(Command::Edge(Direction::Left)),
+            '$' => Some(Command::Edge(Direction::Right)),
+            'g' => Some(Command::Edge(Direction::Up)),
+            'G' => Some(Command::Edge(Direction::Down)),
+            _ => None,
+        };
+        if let Some(command) = direction {
+            apply_command(field, command, Placement::Random, |_| ());
+            continue;
+        }
+        match key {
+            ' ' | 'F' | 'f' => {
+                field.toggle_bomb_at_cursor();
+                saved = false;
+            }
+            'S' => {
+                view.notice = Some(match save_board(field, path) {
+                    Ok(()) => {
+                        saved = true;
+                        format!("Saved to {}", path.display())
+                    }
+                    Err(e) => format!("Can't save to {}: {}", path.display(), e),
+                })
+            }
+            'Q' | 'q'
+                if saved || confirm(confirm_quit, &mut buffer, "Quit without saving? (Y/N): ") =>
+            {
+                break
             }
+            CTRL_C | TERMINATED => break,
+            _ => (),
         }
     }
+}
+
+fn save_game(field: &Field, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, save::serialize(field))
+}
 
-    fn inc_cursor(&mut self, o: Orientation) {
-        match o {
-            Orientation::Vertical => {
-                if self.cursor[0] < self.rows - 1 {
-                    self.cursor[0] += 1;
+fn load_game(path: &Path) -> Result<Field, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    save::deserialize(&text)
+}
+
+fn load_board(path: &Path, seed: u64) -> Result<Field, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    save::parse_board(&text, seed)
+}
+
+fn load_replay(path: &Path) -> Result<Replay, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    replay::parse(&text)
+}
+
+/// Plays a recorded game back at the pace it was played, with long pauses
+/// cut short. Space pauses & resumes, n steps through a paused replay & q
+/// quits:
+fn run_replay(replay: Replay, view: &mut View, keys: &KeyMap) {
+    let Replay { mut field, events } = replay;
+    let mut buffer = [0u8; 1];
+    let mut screen = Screen::new();
+    let mut paused = false;
+    let mut next = 0;
+    let mut due = Instant::now();
+    loop {
+        let over = next == events.len() || view.lost || field.victory();
+        view.notice = Some(if over {
+            format!("Replay over ({} commands)  q: quit", next)
+        } else {
+            format!(
+                "Replay {}/{}{}  Space: {}  n: step  q: quit",
+                next,
+                events.len(),
+                if paused { " (paused)" } else { "" },
+                if paused { "resume" } else { "pause" }
+            )
+        });
+        view.viewport
+            .follow(&field, chrome_rows(view), view.theme.cell_width());
+        screen.draw(render(&field, view));
+        let wait = (!paused && !over).then(|| due.saturating_duration_since(Instant::now()));
+        let step = match read_input(&mut buffer, wait, keys) {
+            Input::Tick => true,
+            Input::Key(' ') if !over => {
+                paused = !paused;
+                if paused {
+                    field.pause_clock();
+                } else {
+                    field.resume_clock();
+                    due = Instant::now();
                 }
+                false
             }
-            Orientation::Horizontal => {
-                if self.cursor[1] < self.cols - 1 {
-                    self.cursor[1] += 1;
-                }
+            Input::Key('n') => paused && !over,
+            Input::Key('Q' | 'q' | CTRL_C | TERMINATED) => return,
+            Input::Resize => {
+                screen.invalidate();
+                false
+            }
+            _ => false,
+        };
+        if !step {
+            continue;
+        }
+        let (at, command) = events[next];
+        next += 1;
+        // The recorded board has its bombs already:
+        match apply_command(&mut field, command, Placement::Random, |_| ()) {
+            Outcome::LostLife => view.notice = Some(lives_notice(&field)),
+            Outcome::Exploded => {
+                view.lost = true;
+                field.stop_clock();
+                field.reveal_mines();
             }
+            _ => (),
+        }
+        if field.victory() {
+            field.stop_clock();
+            field.flag_mines();
+        }
+        if let Some(&(following, _)) = events.get(next) {
+            due = Instant::now() + following.saturating_sub(at).min(REPLAY_MAX_GAP);
         }
     }
+}
 
-    fn reveal_mines(&mut self) {
-        for i in 0..self.rows {
-            for j in 0..self.cols {
-                let mut cell = self.get_cell_mut(i, j);
-                if cell.pipebomb {
-                    cell.state = State::Open;
-                }
-            }
+/// The bot protocol's view of the board: `key value` lines, the grid a row
+/// per line, then a blank line. Cells are `#` closed, `F` flagged, `?`
+/// questioned, their number once open, or `*` for a bomb that went off:
+fn snapshot(field: &Field, view: &View) -> String {
+    let state = if view.lost {
+        "lost"
+    } else if field.victory() {
+        "won"
+    } else {
+        "playing"
+    };
+    let [row, col] = field.cursor();
+    let mut text = format!(
+        "state {}\nsize {} {}\nmines {}\nlives {}\ncursor {} {}\n",
+        state,
+        field.rows(),
+        field.cols(),
+        field.bomb_count(),
+        field.lives(),
+        row,
+        col
+    );
+    if let Some(notice) = &view.notice {
+        text.push_str(&format!("message {}\n", notice));
+    }
+    for r in 0..field.rows() {
+        for c in 0..field.cols() {
+            let cell = field.cell(r, c);
+            text.push(match cell.state {
+                State::Closed => '#',
+                State::Flagged => 'F',
+                State::Questioned => '?',
+                State::Open if field.has_bomb_at(r, c) => '*',
+                State::Open => char::from_digit(cell.adjacent as u32, 10).unwrap(),
+            });
         }
+        text.push('\n');
     }
+    text.push('\n');
+    text
+}
 
-    fn victory(&self) -> bool {
-        for i in 0..self.rows {
-            for j in 0..self.cols {
-                if !self.cells[i][j].pipebomb 
-                && self.cells[i][j].state != State::Open {
-                    return false;
-                }
-            }
+/// Prints the board after a headless command, for a bot or a person:
+fn print_board(field: &Field, view: &View, bot: bool) {
+    if bot {
+        print!("{}", snapshot(field, view));
+    } else {
+        print!("{}", screen::plain(&render(field, view)));
+    }
+}
+
+/// Runs one headless command, returning the notice it leaves, if any:
+fn headless_step(
+    field: &mut Field,
+    view: &mut View,
+    command: Command,
+    placement: Placement,
+) -> Option<String> {
+    if view.no_flag
+        && matches!(
+            command,
+            Command::Flag | Command::Mark | Command::FlagObvious
+        )
+    {
+        return Some(NO_FLAG_NOTICE.to_owned());
+    }
+    match apply_command(field, command, placement, |_| ()) {
+        Outcome::LostLife => Some(lives_notice(field)),
+        Outcome::Exploded => {
+            view.lost = true;
+            field.stop_clock();
+            field.reveal_mines();
+            None
         }
-        return true;
+        Outcome::MayNeedGuess => {
+            Some("No guess-free board found, you may have to guess.".to_owned())
+        }
+        Outcome::NoSuchCell => Some(format!("No such cell: '{}'", command)),
+        Outcome::Stuck => Some(STUCK_NOTICE.to_owned()),
+        Outcome::Done => None,
     }
+}
 
-    fn render(&self) {
-        clear_term!();
-        let vert = {
-            let mut vert = String::new();
-            for i in 0..self.cols {
-                vert.push_str(" _ ");
+/// Plays from commands on stdin, one per line, printing the board after
+/// each, as plain text or as a `--bot` snapshot. Blank lines & `#` comments
+/// are skipped:
+fn run_headless(field: &mut Field, view: &mut View, placement: Placement, bot: bool) {
+    // Scripts already know their commands:
+    view.help_bar = None;
+    // Plain text can only show the cursor with its brackets:
+    view.theme.compact = false;
+    view.theme.cursor = CursorStyle::Brackets;
+    view.viewport = Viewport {
+        top: 0,
+        left: 0,
+        rows: field.rows(),
+        cols: field.cols(),
+        cramped: false,
+        width: usize::MAX,
+    };
+    print_board(field, view, bot);
+    for line in std::io::stdin().lines() {
+        let line = line.unwrap();
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "quit" {
+            break;
+        }
+        // The clock only runs between commands, so that's when time's up:
+        if time_up(field, view) {
+            view.lost = true;
+            field.stop_clock();
+            field.reveal_mines();
+            view.notice = Some(TIME_UP_NOTICE.to_owned());
+            print_board(field, view, bot);
+            if !bot {
+                println!("{}", TIME_UP_NOTICE);
             }
-            vert
+            return;
+        }
+        // A command with a cell moves there first, & stops short if it can't:
+        view.notice = match command::parse_line(line) {
+            Ok(commands) => commands
+                .into_iter()
+                .find_map(|command| headless_step(field, view, command, placement)),
+            Err(e) => Some(e),
         };
-        println!(" {} ", vert);
-        for r in 0..self.rows {
-            print!("|");
-            for c in 0..self.cols {
-                let cursor_here: bool = self.is_cursor_at(r, c);
-                print!(
-                    "{}{}{}",
-                    if cursor_here { "[" } else { " " },
-                    match self.cells[r][c].state {
-                        State::Open => self.cell_str_at(r, c),
-                        State::Closed => CLOSED.to_owned(),
-                        State::Flagged => FLAGGED.to_owned(),
-                    },
-                    if cursor_here { "]" } else { " " }
-                )
-            }
-            println!("|");
+        if field.victory() {
+            field.stop_clock();
+            field.flag_mines();
+        }
+        print_board(field, view, bot);
+        // The snapshot's state says how it ended:
+        if bot && (view.lost || field.victory()) {
+            return;
+        }
+        if view.lost {
+            println!("Whoops!");
+            return;
+        }
+        if field.victory() {
+            println!(
+                "You won! Time: {:.1}s  3BV: {}  Clicks: {}",
+                field.elapsed().as_secs_f64(),
+                field.three_bv(),
+                field.actions()
+            );
+            return;
         }
-        println_flush!(" {} ", vert);
     }
 }
 
-use std::env;
-fn main1() {
-    let args: Vec<String> = env::args().collect();
-    dbg!(args);
-}
-
 // TODO: Add victory conditions
 fn main() {
-    // Set non-canonical mode:
-    let og_attr = Termios::from_fd(STDIN_FILENO).unwrap();
-    let mut new_attr = og_attr.clone();
+    let mut args = args::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("Run with --help for usage.");
+        process::exit(2);
+    });
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+    if args.version {
+        match option_env!("PIPEBOMB_SWEEPER_COMMIT") {
+            Some(commit) => println!(
+                "pipebomb-sweeper {} ({})",
+                env!("CARGO_PKG_VERSION"),
+                commit
+            ),
+            None => println!("pipebomb-sweeper {}", env!("CARGO_PKG_VERSION")),
+        }
+        return;
+    }
+    if args.stats {
+        println!("{}", Stats::load().report(&Scores::load()));
+        return;
+    }
+    let mut config = config::load().unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        process::exit(2);
+    });
+    // Scripts get the headless mode, whichever end of the pipe they're on:
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    // Run bare, the game asks which board to play:
+    if env::args().len() == 1 && interactive {
+        let _raw_mode = enable_raw_mode();
+        terminal::handle_terminate();
+        terminal::handle_resize();
+        if !run_title_screen(&mut args, &config.keys) {
+            return;
+        }
+    }
+    let date = args.daily.then(daily::today);
+    let seed = match &date {
+        Some(date) => daily::seed(date),
+        None => args.seed.unwrap_or_else(|| rand::thread_rng().gen()),
+    };
+    let mode = match &date {
+        Some(date) => Some(format!(
+            "Daily challenge {} ({})  Start: row {} col {}",
+            date,
+            daily::DIFFICULTY.name(),
+            daily::start().0,
+            daily::start().1
+        )),
+        None => args.difficulty.map(|d| d.name().to_owned()),
+    };
+    let title = board_title(mode, &args);
+    if let Some(palette) = args.theme {
+        config.theme.palette = palette;
+    }
+    if let Some(style) = args.cursor {
+        config.theme.cursor = style;
+    }
+    config.theme.compact = args.compact;
+    let bell = args.bell || config.bell;
+    let placement = match args.big_start {
+        _ if args.no_guess => Placement::NoGuess,
+        Some(percent) => Placement::BigStart(percent),
+        None => Placement::Random,
+    };
+    let mut view = View {
+        title,
+        best: None,
+        color: args.color,
+        theme: config.theme,
+        countdown: args.countdown.map(Duration::from_secs),
+        no_flag: args.no_flag,
+        lost: false,
+        // The keys, as bound, until the first key. The help bar already
+        // shows them:
+        notice: (!args.help_bar && !args.quiet)
+            .then(|| format!("{}  More: --help", config.keys.help())),
+        hint: false,
+        training: args.training,
+        reveal_all: false,
+        paused: false,
+        odds: None,
+        odds_generation: None,
+        viewport: Viewport::default(),
+        tally: Tally::default(),
+        summary: None,
+        help_bar: (args.help_bar && !args.quiet)
+            .then(|| help_bar(&args, &config.keys, args.difficulty)),
+        quiet: args.quiet,
+    };
+
+    if let Some(path) = &args.replay {
+        let replay = load_replay(path).unwrap_or_else(|e| {
+            eprintln!("Can't load {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        if !interactive {
+            eprintln!("--replay needs a terminal");
+            process::exit(1);
+        }
+        let _raw_mode = enable_raw_mode();
+        terminal::handle_terminate();
+        terminal::handle_resize();
+        view.title = Some(format!("Replay of {}", path.display()));
+        // The game's keys & modes don't apply here:
+        view.help_bar = None;
+        run_replay(replay, &mut view, &config.keys);
+        return;
+    }
+
+    let load = if args.resume {
+        Some(autosave_path().unwrap_or_else(|| {
+            eprintln!("Can't find the autosave: no data directory");
+            process::exit(1);
+        }))
+    } else {
+        args.load.clone()
+    };
+    let mut main_field = match &load {
+        Some(path) => load_game(path).unwrap_or_else(|e| {
+            eprintln!("Can't load {}: {}", path.display(), e);
+            process::exit(1);
+        }),
+        None => {
+            let mut field = match (&args.board, &args.code) {
+                (Some(path), _) => load_board(path, seed).unwrap_or_else(|e| {
+                    eprintln!("Can't load {}: {}", path.display(), e);
+                    process::exit(1);
+                }),
+                // A board being edited starts out empty, unless it's on disk:
+                _ if args.edit.as_ref().is_some_and(|path| path.exists()) => {
+                    let path = args.edit.as_ref().unwrap();
+                    load_board(path, seed).unwrap_or_else(|e| {
+                        eprintln!("Can't load {}: {}", path.display(), e);
+                        process::exit(1);
+                    })
+                }
+                _ if args.edit.is_some() => {
+                    check_dimensions(args.rows, args.cols).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        process::exit(2);
+                    });
+                    Field::with_bombs(args.rows, args.cols, &[], seed)
+                }
+                (_, Some(code)) => Field::from_code(code).unwrap_or_else(|e| {
+                    eprintln!("Invalid board code: {}", e);
+                    process::exit(2);
+                }),
+                _ => Field::try_new(args.rows, args.cols, args.density, args.mines, seed)
+                    .unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        eprintln!("Run with --help for usage.");
+                        process::exit(2);
+                    }),
+            };
+            field.set_wrap(args.wrap || args.torus);
+            field.set_torus(args.torus);
+            field.set_lives(args.lives);
+            field.set_win_on_flags(args.win_on_flags);
+            // The daily board starts at its start cell, retries included:
+            field.set_center_start(args.center_start || args.daily);
+            field.randomize();
+            if args.daily {
+                let (row, col) = daily::start();
+                field.fix_bombs(row, col);
+            }
+            field
+        }
+    };
+
+    if args.edit.is_some() && !interactive {
+        eprintln!("--edit needs a terminal");
+        process::exit(1);
+    }
+    if args.headless || args.bot || !interactive {
+        view.color = false;
+        view.notice = None;
+        run_headless(&mut main_field, &mut view, placement, args.bot);
+        return;
+    }
 
-    new_attr.c_lflag &= !(ICANON | ECHO);
-    tcsetattr(STDIN_FILENO, TCSANOW, &mut new_attr).unwrap();
+    let _raw_mode = enable_raw_mode();
+    let _mouse = terminal::MouseCapture::enable();
+    terminal::handle_terminate();
+    terminal::handle_resize();
+    if let Some(path) = &args.edit {
+        view.title = Some(format!("Editing {}", path.display()));
+        run_editor(&mut main_field, &mut view, path, &config.keys, args.confirm);
+        return;
+    }
     let mut buffer = [0u8; 1]; // To read exactly one byte (key, char, etc)
 
-    let args: Vec<String> = env::args().collect();
-    let rows = args[1].parse::<usize>().unwrap_or_else(|_| 8);
-    let cols = args[2].parse::<usize>().unwrap_or_else(|_| 8);
-    let bomb_pcnt = args[3].parse::<usize>().unwrap_or_else(|_| 16);
-
-    let mut main_field = Field::new(rows, cols, bomb_pcnt);
-
-    main_field.randomize();
-    main_field.render();
-    let mut quit = false;
-    let mut victory = false;
-    let mut game_over = false;
-    while !quit {
-        std::io::stdin().read_exact(&mut buffer).unwrap();
-
-        match buffer[0] as char {
-            'A' | 'a' => main_field.dec_cursor(Orientation::Horizontal),
-            'W' | 'w' => main_field.dec_cursor(Orientation::Vertical),
-            'S' | 's' => main_field.inc_cursor(Orientation::Vertical),
-            'D' | 'd' => main_field.inc_cursor(Orientation::Horizontal),
-            'F' | 'f' => main_field.flag_at_cursor(),
-            ' ' => {
-                if main_field.open_at_cursor(&mut buffer) {
-                    game_over = true
+    let mut scores = Scores::load();
+    let mut stats = Stats::load();
+    let mut score_key = scores::board_key(&main_field);
+    view.best = scores.best(&score_key);
+
+    let animate = args.animate.map(Duration::from_millis);
+    let mut screen = Screen::new();
+    view.viewport
+        .follow(&main_field, chrome_rows(&view), view.theme.cell_width());
+    screen.draw(render(&main_field, &view));
+    let mut phase = Phase::Playing;
+    // The game's result, to go back to from the difficulty menu:
+    let mut result = None;
+    // Set once the solver has played in the current game or the board has
+    // been revealed, & throughout `--training` games, which then aren't
+    // recorded in the best times or statistics:
+    let mut assisted = args.training;
+    let mut autosaved = None;
+    // Only games played from the start are recorded, so a resumed one isn't:
+    let mut recorder = args
+        .record
+        .as_ref()
+        .filter(|_| main_field.is_first_move())
+        .map(|_| Recorder::start());
+    // `--autosolve` starts the game as if '!' was pressed:
+    let mut pending = args.autosolve.then_some('!');
+    while phase != Phase::Quit {
+        // A click moves the cursor to the cell & acts like the key for its
+        // button:
+        let input = match pending.take() {
+            Some(key) => Input::Key(key),
+            // While the clock runs, the time shown is kept current:
+            None => read_input(
+                &mut buffer,
+                main_field.clock_running().then_some(TICK),
+                &config.keys,
+            ),
+        };
+        let key = match input {
+            Input::Key(key) => key,
+            Input::Click(button, row, col) => match cell_at(&main_field, &view, row, col) {
+                Some((row, col)) => {
+                    main_field.set_cursor(row, col);
+                    match button {
+                        MouseButton::Left => ' ',
+                        MouseButton::Middle => 'c',
+                        MouseButton::Right => 'f',
+                    }
+                }
+                None => continue,
+            },
+            // The old frame's layout no longer matches the screen, so it's
+            // redrawn from scratch, fitted to the new size:
+            Input::Resize => {
+                view.viewport
+                    .follow(&main_field, chrome_rows(&view), view.theme.cell_width());
+                screen.invalidate();
+                screen.draw(render(&main_field, &view));
+                continue;
+            }
+            Input::Tick if !time_up(&main_field, &view) => {
+                screen.draw(render(&main_field, &view));
+                continue;
+            }
+            Input::Tick => TIME_UP,
+        };
+        // Running out of time ends the game like a bomb would, whatever key
+        // comes in too late, or none:
+        let key = if phase == Phase::Playing && time_up(&main_field, &view) {
+            TIME_UP
+        } else {
+            key
+        };
+        // A finished game only takes the end-of-game menu's keys, & keeps
+        // its result shown until then. A lost one can still be reviewed:
+        if phase == Phase::GameOver
+            && !matches!(key, 'R' | 'r' | 'B' | 'b' | 'Q' | 'q' | CTRL_C | TERMINATED)
+            && !(view.lost && is_review_key(key))
+        {
+            continue;
+        }
+        if phase == Phase::Menu {
+            let difficulty = match key {
+                '1' => Some(Difficulty::Beginner),
+                '2' => Some(Difficulty::Intermediate),
+                '3' => Some(Difficulty::Expert),
+                _ => None,
+            };
+            match (key, difficulty) {
+                (_, Some(difficulty)) => {
+                    main_field = board_like(&main_field, difficulty);
+                    view.odds_generation = None;
+                    view.title = board_title(Some(difficulty.name().to_owned()), &args);
+                    if view.help_bar.is_some() {
+                        view.help_bar = Some(help_bar(&args, &config.keys, Some(difficulty)));
+                    }
+                    score_key = scores::board_key(&main_field);
+                    view.best = scores.best(&score_key);
+                    view.lost = false;
+                    view.summary = None;
+                    view.notice = None;
+                    assisted = args.training;
+                    if args.record.is_some() {
+                        recorder = Some(Recorder::start());
+                    }
+                    phase = Phase::Playing;
+                    screen.invalidate();
+                }
+                (CTRL_C, _) => phase = Phase::Quit,
+                (TERMINATED, _) => (),
+                // Esc reads as q:
+                (_, None) => {
+                    view.notice = result.clone();
+                    phase = Phase::GameOver;
+                }
+            }
+            if terminated() {
+                break;
+            }
+            view.viewport
+                .follow(&main_field, chrome_rows(&view), view.theme.cell_width());
+            screen.draw(render(&main_field, &view));
+            continue;
+        }
+        // A paused game only takes a resume or a quit:
+        if view.paused && !matches!(key, 'Z' | 'z' | 'Q' | 'q' | CTRL_C | TERMINATED) {
+            continue;
+        }
+        if phase == Phase::Playing {
+            view.notice = None;
+        }
+        view.hint = false;
+        view.reveal_all = false;
+        let mut command = None;
+        let mut game_over = false;
+        match key {
+            'A' | 'a' | 'H' | 'h' => command = Some(Command::Move(Direction::Left)),
+            'W' | 'w' | 'K' | 'k' => command = Some(Command::Move(Direction::Up)),
+            's' | 'J' | 'j' => command = Some(Command::Move(Direction::Down)),
+            'D' | 'd' | 'L' | 'l' => command = Some(Command::Move(Direction::Right)),
+            '0' => command = Some(Command::Edge(Direction::Left)),
+            '$' => command = Some(Command::Edge(Direction::Right)),
+            'g' => command = Some(Command::Edge(Direction::Up)),
+            'G' => command = Some(Command::Edge(Direction::Down)),
+            ':' => {
+                if let Some(text) = read_line(&mut buffer, "Go to (row col): ") {
+                    match parse_coordinate(&main_field, &text) {
+                        Some((row, col)) => command = Some(Command::GoTo(row, col)),
+                        None => view.notice = Some(format!("No such cell: '{}'", text.trim())),
+                    }
+                }
+            }
+            'F' | 'f' if view.no_flag => view.notice = Some(NO_FLAG_NOTICE.to_owned()),
+            'F' | 'f' => command = Some(Command::Flag),
+            'M' | 'm' if view.no_flag => view.notice = Some(NO_FLAG_NOTICE.to_owned()),
+            'M' | 'm' => command = Some(Command::FlagObvious),
+            '?' => match solver::safe_hint(&main_field) {
+                Some((row, col)) => {
+                    main_field.set_cursor(row, col);
+                    view.hint = true;
+                }
+                None => view.notice = Some("No safe move — you'll have to guess.".to_owned()),
+            },
+            'X' => {
+                view.reveal_all = true;
+                assisted = true;
+            }
+            'Z' | 'z' => {
+                view.paused = !view.paused;
+                if view.paused {
+                    main_field.pause_clock();
+                    view.notice = Some(PAUSED_NOTICE.to_owned());
                 } else {
-                    main_field.check_at(main_field.cursor[0], main_field.cursor[1]);
+                    main_field.resume_clock();
+                }
+            }
+            'P' | 'p' => {
+                view.odds = match view.odds {
+                    Some(_) => None,
+                    None => Some(HashMap::new()),
+                };
+                view.odds_generation = None;
+            }
+            '!' => {
+                command = Some(Command::Solve);
+                assisted = true;
+            }
+            'S' => {
+                let default = save_path();
+                let prompt = match &default {
+                    Some(path) => format!("Save to [{}]: ", path.display()),
+                    None => "Save to: ".to_owned(),
+                };
+                if let Some(text) = read_line(&mut buffer, &prompt) {
+                    let path = match text.trim() {
+                        "" => default,
+                        text => Some(PathBuf::from(text)),
+                    };
+                    view.notice = Some(match path {
+                        Some(path) => match save_game(&main_field, &path) {
+                            Ok(()) => format!("Saved to {}", path.display()),
+                            Err(e) => format!("Can't save to {}: {}", path.display(), e),
+                        },
+                        None => "No save location".to_owned(),
+                    });
+                }
+            }
+            'E' | 'e' => view.notice = Some(format!("Board code: {}", main_field.to_code())),
+            'U' | 'u' => command = Some(Command::Undo),
+            'C' | 'c' => command = Some(Command::Chord),
+            ' ' => {
+                let [row, col] = main_field.cursor();
+                if main_field.cell(row, col).state != State::Flagged
+                    || confirm(
+                        args.confirm,
+                        &mut buffer,
+                        "Are you sure you want to open this flagged cell? (Y/N): ",
+                    )
+                {
+                    command = Some(Command::Open);
                 }
             }
-            // ' ' => main_field.check_at(main_field.cursor[0], main_field.cursor[1]),
             'R' | 'r' => {
-                print_flush!("{}", "\nAre you sure you want to reset? (Y/N): ");
-                loop {
-                    std::io::stdin().read_exact(&mut buffer).unwrap();
-                    match buffer[0] as char {
-                        'Y' | 'y' => {
-                            main_field.randomize();
-                            break;
-                        }
-                        'N' | 'n' => {
-                            break;
-                        }
-                        _ => (),
-                    }
+                let (reset, prompt) = match key {
+                    'R' => (
+                        Command::Retry,
+                        "Are you sure you want to retry this board? (Y/N): ",
+                    ),
+                    _ => (Command::Reset, "Are you sure you want to reset? (Y/N): "),
+                };
+                if phase == Phase::GameOver || confirm(args.confirm, &mut buffer, prompt) {
+                    command = Some(reset);
+                    view.lost = false;
+                    view.summary = None;
+                    view.notice = None;
+                    phase = Phase::Playing;
+                    assisted = args.training;
+                    screen.invalidate();
                 }
             }
+            'B' | 'b' if phase == Phase::GameOver => {
+                view.notice = Some(DIFFICULTY_MENU.to_owned());
+                phase = Phase::Menu;
+            }
             'Q' | 'q' => {
-                print_flush!("{}", "\nAre you sure you want to quit? (Y/N): ");
-                loop {
-                    std::io::stdin().read_exact(&mut buffer).unwrap();
-                    match buffer[0] as char {
-                        'Y' | 'y' => {
-                            quit = true;
-                            break;
-                        }
-                        'N' | 'n' => {
-                            break;
-                        }
-                        _ => (),
+                if phase == Phase::GameOver
+                    || confirm(
+                        args.confirm,
+                        &mut buffer,
+                        "Are you sure you want to quit? (Y/N): ",
+                    )
+                {
+                    phase = Phase::Quit;
+                }
+            }
+            CTRL_C => phase = Phase::Quit,
+            TIME_UP => game_over = true,
+            TERMINATED => (),
+            _ if args.quiet => (),
+            _ => view.notice = Some("??? what".to_owned()),
+        }
+        if let Some(command) = command {
+            // Redraws the opening as it spreads, if animated, & the solver's
+            // every step:
+            let delay = match command {
+                Command::Solve => Some(animate.unwrap_or(SOLVE_STEP)),
+                _ => animate,
+            };
+            let on_ring = |field: &Field| {
+                if let Some(delay) = delay {
+                    view.viewport
+                        .follow(field, chrome_rows(&view), view.theme.cell_width());
+                    screen.draw(render(field, &view));
+                    std::thread::sleep(delay);
+                }
+            };
+            let cursor = main_field.cursor();
+            match apply_command(&mut main_field, command, placement, on_ring) {
+                Outcome::Exploded => game_over = true,
+                Outcome::LostLife => {
+                    if bell {
+                        print_flush!("\x07");
                     }
+                    view.notice = Some(lives_notice(&main_field));
+                }
+                Outcome::MayNeedGuess => {
+                    view.notice =
+                        Some("No guess-free board found, you may have to guess.".to_owned())
                 }
+                Outcome::Stuck => view.notice = Some(STUCK_NOTICE.to_owned()),
+                Outcome::Done | Outcome::NoSuchCell => (),
+            }
+            // A new board or a retry is a new game to record:
+            match command {
+                Command::Reset | Command::Retry if args.record.is_some() => {
+                    recorder = Some(Recorder::start())
+                }
+                _ => {
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record(command, cursor, &main_field);
+                    }
+                }
+            }
+        }
+        // The overlay follows every change to the board, but only those:
+        if view.odds.is_some() && phase == Phase::Playing {
+            if view.odds_generation != Some(main_field.generation()) {
+                view.odds = Some(solver::probabilities(&main_field));
+                view.odds_generation = Some(main_field.generation());
+            }
+            if let (None, Some(odds)) = (&view.notice, &view.odds) {
+                view.notice = Some(odds_notice(odds));
             }
-            _ => println!("??? what"),
         }
+        let was_playing = phase == Phase::Playing;
         if game_over {
+            // For a bomb, not for running out of time:
+            if bell && key != TIME_UP {
+                print_flush!("\x07");
+            }
+            view.lost = true;
+            main_field.stop_clock();
+            view.summary = Some(summary(&main_field, &view));
+            if !assisted {
+                stats.record(false, main_field.elapsed(), main_field.three_bv());
+            }
             main_field.reveal_mines();
-            quit = true;
+            view.tally.lost += 1;
+            view.notice = Some(format!(
+                "{} Move & open to review the board  {}",
+                if key == TIME_UP {
+                    TIME_UP_NOTICE
+                } else {
+                    "Whoops!"
+                },
+                END_MENU
+            ));
+            phase = Phase::GameOver;
+        } else if phase == Phase::Playing && main_field.victory() {
+            main_field.stop_clock();
+            view.summary = Some(summary(&main_field, &view));
+            main_field.flag_mines();
+            let mut new_record = false;
+            if !assisted {
+                stats.record(true, main_field.elapsed(), main_field.three_bv());
+                new_record = scores.record(&score_key, main_field.elapsed());
+                view.best = scores.best(&score_key);
+            }
+            view.tally.won += 1;
+            let note = if assisted {
+                "  (assisted, not recorded)"
+            } else if new_record {
+                "  New best time!"
+            } else {
+                ""
+            };
+            view.notice = Some(format!("You won!{}  {}", note, END_MENU));
+            phase = Phase::GameOver;
         }
-        if main_field.victory() {
-            main_field.reveal_mines();
-            victory = true;
-            quit = true;
+        if phase == Phase::GameOver && was_playing {
+            result = view.notice.clone();
+            if let (Some(recorder), Some(path)) = (&recorder, &args.record) {
+                if let Err(e) = fs::write(path, recorder.serialize(&main_field)) {
+                    view.notice = Some(format!(
+                        "Can't save the replay to {}: {}",
+                        path.display(),
+                        e
+                    ));
+                }
+            }
         }
-        main_field.render();
+        if terminated() {
+            if phase == Phase::Playing {
+                autosaved = autosave_path().filter(|path| save_game(&main_field, path).is_ok());
+            }
+            break;
+        }
+        view.viewport
+            .follow(&main_field, chrome_rows(&view), view.theme.cell_width());
+        screen.draw(render(&main_field, &view));
     }
 
-    if game_over {
-        println!("\nWhoops!");
-    } else if victory {
-        println!("You won!")
-    } else {
+    // Even --quiet reports an autosave, or the game would seem lost:
+    if let Some(path) = autosaved {
+        println!("\nSaved to {}, continue with --resume.", path.display());
+    } else if !args.quiet {
         println!("\nBye-bye!");
     }
+    if !args.quiet {
+        println!("Won: {}  Lost: {}", view.tally.won, view.tally.lost);
+        println!("Seed: {}", main_field.seed());
+    }
+}
 
-    tcsetattr(STDIN_FILENO, TCSANOW, &og_attr).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_yes_no_and_cancel_keys_answer_a_prompt() {
+        assert_eq!(answer(b'y', false), Some(true));
+        assert_eq!(answer(b'Y', false), Some(true));
+        assert_eq!(answer(b'n', false), Some(false));
+        assert_eq!(answer(0x03, false), Some(false));
+        assert_eq!(answer(ESC, true), Some(false));
+        // An arrow key's escape sequence, or any other key, is no answer:
+        assert_eq!(answer(ESC, false), None);
+        assert_eq!(answer(b'q', false), None);
+        assert_eq!(answer(b'\r', false), None);
+    }
 }
@@ -0,0 +1,156 @@
+//! Recorded games, to watch again with `--replay`. A replay is text: a
+//! header, the board as a `--code` code with its bombs placed, the options
+//! that change how it plays, then one `<ms> <command>` line per command,
+//! timed from the start of the game & written as in the headless mode:
+//!
+//! ```text
+//! pipebomb-sweeper replay 1
+//! code AQAIAAgAAAAK...
+//! wrap false
+//! torus false
+//! lives 1
+//! win_on_flags false
+//! 0 goto 3 4
+//! 0 open
+//! 1520 move right
+//! ```
+
+use std::time::{Duration, Instant};
+
+use pipebomb_sweeper::save::{field_value, parse_value};
+use pipebomb_sweeper::Field;
+
+use crate::command::Command;
+
+const HEADER: &str = "pipebomb-sweeper replay 1";
+
+/// Notes the commands of one game as they're played:
+pub struct Recorder {
+    start: Instant,
+    // Where the last command left the cursor. Clicks & hints move it
+    // without a command, so a `goto` is slipped in when it has moved since:
+    cursor: [usize; 2],
+    events: Vec<(Duration, Command)>,
+}
+
+impl Recorder {
+    /// Starts recording a game, from now:
+    pub fn start() -> Self {
+        Recorder {
+            start: Instant::now(),
+            // Played back, the board starts with the cursor in the corner:
+            cursor: [0, 0],
+            events: Vec::new(),
+        }
+    }
+
+    /// Notes `command`, given with the cursor at `cursor` & since applied to
+    /// `field`:
+    pub fn record(&mut self, command: Command, cursor: [usize; 2], field: &Field) {
+        let at = self.start.elapsed();
+        if cursor != self.cursor {
+            self.events.push((at, Command::GoTo(cursor[0], cursor[1])));
+        }
+        self.events.push((at, command));
+        self.cursor = field.cursor();
+    }
+
+    /// The replay of the game played on `field`, once it's over:
+    pub fn serialize(&self, field: &Field) -> String {
+        let mut text = format!(
+            "{}\ncode {}\nwrap {}\ntorus {}\nlives {}\nwin_on_flags {}\n",
+            HEADER,
+            field.to_code(),
+            field.is_wrap(),
+            field.is_torus(),
+            field.max_lives(),
+            field.is_win_on_flags(),
+        );
+        for (at, command) in &self.events {
+            text.push_str(&format!("{} {}\n", at.as_millis(), command));
+        }
+        text
+    }
+}
+
+/// A game to play back: its board, closed, & every command at its time:
+pub struct Replay {
+    pub field: Field,
+    pub events: Vec<(Duration, Command)>,
+}
+
+/// The `name value` line expected next, parsed:
+fn value<'a, T: std::str::FromStr>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<T, String> {
+    parse_value(name, field_value(lines, name)?)
+}
+
+pub fn parse(text: &str) -> Result<Replay, String> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err("Not a replay file".to_owned());
+    }
+    let mut field = Field::from_code(field_value(&mut lines, "code")?)?;
+    field.set_wrap(value(&mut lines, "wrap")?);
+    field.set_torus(value(&mut lines, "torus")?);
+    field.set_lives(value(&mut lines, "lives")?);
+    field.set_win_on_flags(value(&mut lines, "win_on_flags")?);
+    let events = lines
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (at, command) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("Invalid event: '{}'", line))?;
+            let at = at.parse().map_err(|_| format!("Invalid time: '{}'", at))?;
+            Ok((Duration::from_millis(at), command.parse()?))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Replay { field, events })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Direction;
+
+    #[test]
+    fn a_recorded_game_parses_back_unchanged() {
+        let mut field = Field::new(6, 9, 0, Some(10), 42);
+        field.set_wrap(true);
+        field.set_lives(2);
+        let mut recorder = Recorder::start();
+        field.place_bombs_excluding(2, 4);
+        field.set_cursor(2, 4);
+        field.open_at_cursor();
+        // Opened by a click, so with a `goto` slipped in first:
+        recorder.record(Command::Open, [2, 4], &field);
+        field.inc_cursor(pipebomb_sweeper::Orientation::Horizontal);
+        recorder.record(Command::Move(Direction::Right), [2, 4], &field);
+        let text = recorder.serialize(&field);
+
+        let replay = parse(&text).unwrap();
+        assert_eq!(replay.field.to_code(), field.to_code());
+        assert!(replay.field.is_wrap() && !replay.field.is_torus());
+        assert_eq!(replay.field.max_lives(), 2);
+        let events: Vec<String> = replay
+            .events
+            .iter()
+            .map(|(_, command)| command.to_string())
+            .collect();
+        assert_eq!(events, ["goto 2 4", "open", "move right"]);
+        for ((at, _), (recorded, _)) in replay.events.iter().zip(&recorder.events) {
+            assert_eq!(at.as_millis(), recorded.as_millis());
+        }
+    }
+
+    #[test]
+    fn damaged_replays_are_rejected() {
+        let text = Recorder::start().serialize(&Field::new(5, 5, 0, Some(3), 0));
+        assert!(parse(&text).is_ok());
+        assert!(parse(&text.replace("replay 1", "replay 9")).is_err());
+        assert!(parse(&text.replace("lives 1", "lives many")).is_err());
+        assert!(parse(&format!("{}12 explode\n", text)).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Saved games: a small line-based text format holding everything needed to
+//! resume a `Field` mid-game. The grid is one line per row, one character per
+//! cell: `c`losed, `o`pen, `f`lagged or `q`uestioned, uppercase if the cell
+//! holds a bomb. Undo history isn't kept.
+//!
+//! Hand-made puzzle boards are simpler: one line per row, `*` for a mine and
+//! `.` for a safe cell.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{Cell, Field, State};
+
+const HEADER: &str = "pipebomb-sweeper save 4";
+// Older saves, from before the centered start (cornered), before lives (one
+// each) & before the torus variant:
+const HEADER_V3: &str = "pipebomb-sweeper save 3";
+const HEADER_V2: &str = "pipebomb-sweeper save 2";
+const HEADER_V1: &str = "pipebomb-sweeper save 1";
+
+pub fn serialize(field: &Field) -> String {
+    let mut text = format!(
+        "{}\nrows {}\ncols {}\nmines {}\nseed {}\ncursor {} {}\nfirst_move {}\nelapsed_ms {}\nwrap {}\ntorus {}\nlives {} {}\nwin_on_flags {}\nfixed {}\ncenter_start {}\n",
+        HEADER,
+        field.rows,
+        field.cols,
+        field.bomb_count,
+        field.seed,
+        field.cursor[0],
+        field.cursor[1],
+        field.first_move,
+        field.elapsed().as_millis(),
+        field.wrap,
+        field.torus,
+        field.lives,
+        field.max_lives,
+        field.win_on_flags,
+        field.fixed,
+        field.center_start,
+    );
+    for row in field.cells.chunks(field.cols.max(1)) {
+        for cell in row {
+            let code = match cell.state {
+                State::Closed => 'c',
+                State::Open => 'o',
+                State::Flagged => 'f',
+                State::Questioned => 'q',
+            };
+            text.push(if cell.pipebomb {
+                code.to_ascii_uppercase()
+            } else {
+                code
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Value of the `name value` line expected next. Also read by the game's
+/// replay files, which share the layout:
+pub fn field_value<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<&'a str, String> {
+    lines
+        .next()
+        .and_then(|line| line.strip_prefix(name))
+        .and_then(|rest| rest.strip_prefix(' '))
+        .ok_or_else(|| format!("Missing '{}'", name))
+}
+
+pub fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {}: '{}'", name, value))
+}
+
+/// Rebuilds the exact position written by `serialize`, with the clock running
+/// from the saved elapsed time:
+pub fn deserialize(text: &str) -> Result<Field, String> {
+    let mut lines = text.lines();
+    let version = match lines.next() {
+        Some(HEADER) => 4,
+        Some(HEADER_V3) => 3,
+        Some(HEADER_V2) => 2,
+        Some(HEADER_V1) => 1,
+        _ => return Err("Not a pipebomb-sweeper save file".to_owned()),
+    };
+    let rows: usize = parse_value("rows", field_value(&mut lines, "rows")?)?;
+    let cols: usize = parse_value("cols", field_value(&mut lines, "cols")?)?;
+    let bomb_count: usize = parse_value("mines", field_value(&mut lines, "mines")?)?;
+    let seed: u64 = parse_value("seed", field_value(&mut lines, "seed")?)?;
+    let (cursor_row, cursor_col) = field_value(&mut lines, "cursor")?
+        .split_once(' ')
+        .ok_or("Invalid cursor")?;
+    let cursor = [
+        parse_value("cursor", cursor_row)?,
+        parse_value("cursor", cursor_col)?,
+    ];
+    let first_move: bool = parse_value("first_move", field_value(&mut lines, "first_move")?)?;
+    let elapsed_ms: u64 = parse_value("elapsed_ms", field_value(&mut lines, "elapsed_ms")?)?;
+    let wrap: bool = parse_value("wrap", field_value(&mut lines, "wrap")?)?;
+    let torus: bool = match version {
+        1 => false,
+        _ => parse_value("torus", field_value(&mut lines, "torus")?)?,
+    };
+    let (lives, max_lives) = match version {
+        1 | 2 => (1, 1),
+        _ => {
+            let (lives, max_lives) = field_value(&mut lines, "lives")?
+                .split_once(' ')
+                .ok_or("Invalid lives")?;
+            (
+                parse_value("lives", lives)?,
+                parse_value("lives", max_lives)?,
+            )
+        }
+    };
+    if lives == 0 || lives > max_lives {
+        return Err("Invalid lives".to_owned());
+    }
+    let win_on_flags: bool = parse_value("win_on_flags", field_value(&mut lines, "win_on_flags")?)?;
+    let fixed: bool = parse_value("fixed", field_value(&mut lines, "fixed")?)?;
+    let center_start: bool = match version {
+        1..=3 => false,
+        _ => parse_value("center_start", field_value(&mut lines, "center_start")?)?,
+    };
+    crate::check_dimensions(rows, cols)?;
+    if cursor[0] >= rows || cursor[1] >= cols || bomb_count >= rows * cols {
+        return Err("Invalid cursor or mine count".to_owned());
+    }
+
+    let mut cells = Vec::with_capacity(rows * cols);
+    for _ in 0..rows {
+        let line = lines.next().ok_or("Grid is too short")?;
+        if line.chars().count() != cols {
+            return Err("Grid row has the wrong width".to_owned());
+        }
+        for code in line.chars() {
+            let state = match code.to_ascii_lowercase() {
+                'c' => State::Closed,
+                'o' => State::Open,
+                'f' => State::Flagged,
+                'q' => State::Questioned,
+                _ => return Err(format!("Invalid cell: '{}'", code)),
+            };
+            cells.push(Cell {
+                state,
+                pipebomb: code.is_ascii_uppercase(),
+                adjacent: 0,
+            });
+        }
+    }
+
+    // Before the first open there are no bombs yet, only their count, unless
+    // they came with a hand-made board:
+    let placed = cells.iter().filter(|cell| cell.pipebomb).count();
+    let expected = if first_move && !fixed { 0 } else { bomb_count };
+    if placed != expected {
+        return Err("Grid doesn't match the mine count".to_owned());
+    }
+    let start = if first_move {
+        None
+    } else {
+        Instant::now().checked_sub(Duration::from_millis(elapsed_ms))
+    };
+    let mut field = Field {
+        rows,
+        cols,
+        cells,
+        bomb_count,
+        unopened_safe_cells: 0,
+        opened: 0,
+        flags: 0,
+        // Not saved: a resumed game counts its clicks afresh:
+        actions: 0,
+        action_pending: false,
+        three_bv: 0,
+        generation: 0,
+        cursor,
+        center_start,
+        wrap,
+        torus,
+        lives,
+        max_lives,
+        win_on_flags,
+        first_move,
+        fixed,
+        start,
+        stop: None,
+        paused: None,
+        seed,
+        rng: StdRng::seed_from_u64(seed),
+        history: VecDeque::new(),
+    };
+    field.update_adjacent();
+    field.count_states();
+    Ok(field)
+}
+
+/// The bombs as a puzzle board, as read by `parse_board`:
+pub fn board_text(field: &Field) -> String {
+    let mut text = String::with_capacity(field.rows * (field.cols + 1));
+    for row in field.cells.chunks(field.cols.max(1)) {
+        text.extend(row.iter().map(|cell| if cell.pipebomb { '*' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
+
+/// Parses a puzzle board into a closed field with its bombs in place. Spaces
+/// are ignored, so boards may be spaced out for readability:
+pub fn parse_board(text: &str, seed: u64) -> Result<Field, String> {
+    let mut bombs = Vec::new();
+    let mut rows = 0;
+    let mut cols = None;
+    for line in text.lines() {
+        let line: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+        if line.is_empty() {
+            continue;
+        }
+        if rows == crate::MAX_SIDE {
+            return Err(format!("More than {} rows", crate::MAX_SIDE));
+        }
+        if *cols.get_or_insert(line.len()) != line.len() {
+            return Err(format!("Row {} has a different width", rows));
+        }
+        for (col, &c) in line.iter().enumerate() {
+            match c {
+                '*' => bombs.push((rows, col)),
+                '.' => (),
+                _ => return Err(format!("Invalid cell: '{}'", c)),
+            }
+        }
+        rows += 1;
+    }
+    let cols = cols.ok_or("The board is empty")?;
+    crate::check_dimensions(rows, cols)?;
+    if bombs.len() == rows * cols {
+        return Err("The board has no safe cell".to_owned());
+    }
+    Ok(Field::with_bombs(rows, cols, &bombs, seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played() -> Field {
+        let mut field = Field::new(6, 9, 0, Some(10), 42);
+        field.place_bombs_excluding(2, 4);
+        field.set_cursor(2, 4);
+        field.open_at_cursor();
+        let (row, col) = (0..6)
+            .flat_map(|row| (0..9).map(move |col| (row, col)))
+            .find(|&(row, col)| field.cells[row * 9 + col].pipebomb)
+            .unwrap();
+        field.flag_at(row, col);
+        field
+    }
+
+    #[test]
+    fn a_saved_game_loads_back_unchanged() {
+        let field = played();
+        let text = serialize(&field);
+        let loaded = deserialize(&text).unwrap();
+        let without_clock = |text: &str| {
+            text.lines()
+                .filter(|line| !line.starts_with("elapsed_ms"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        assert_eq!(without_clock(&serialize(&loaded)), without_clock(&text));
+        assert_eq!(loaded.cursor, field.cursor);
+        assert_eq!(loaded.unopened_safe_cells, field.unopened_safe_cells);
+        assert_eq!(loaded.three_bv(), field.three_bv());
+        assert!(!loaded.first_move);
+    }
+
+    #[test]
+    fn an_unopened_game_keeps_only_its_mine_count() {
+        let field = Field::new(5, 5, 0, Some(4), 7);
+        let mut loaded = deserialize(&serialize(&field)).unwrap();
+        assert!(loaded.first_move);
+        assert_eq!(loaded.bomb_count, 4);
+        loaded.place_bombs_excluding(2, 2);
+        assert_eq!(loaded.cells.iter().filter(|cell| cell.pipebomb).count(), 4);
+    }
+
+    #[test]
+    fn damaged_saves_are_rejected() {
+        let text = serialize(&played());
+        assert!(deserialize("").is_err());
+        assert!(deserialize(&text.replace("rows 6", "rows 7")).is_err());
+        assert!(deserialize(&text.replace("mines 10", "mines 11")).is_err());
+        assert!(deserialize(&text[..text.len() - 5]).is_err());
+    }
+
+    #[test]
+    fn puzzle_boards_round_trip() {
+        let field = parse_board("* . .\n. . .\n. . *\n", 0).unwrap();
+        assert_eq!(board_text(&field), "*..\n...\n..*\n");
+        assert!(parse_board("*.\n...\n", 0).is_err());
+        assert!(parse_board("*x\n", 0).is_err());
+        assert!(parse_board("\n\n", 0).is_err());
+        assert_eq!(
+            parse_board("**\n**\n", 0).err().as_deref(),
+            Some("The board has no safe cell")
+        );
+    }
+
+    #[test]
+    fn the_centered_start_is_kept() {
+        let mut field = played();
+        field.set_center_start(true);
+        assert!(deserialize(&serialize(&field)).unwrap().is_center_start());
+        let v3 = serialize(&field)
+            .replace(HEADER, HEADER_V3)
+            .replace("center_start true\n", "");
+        assert!(!deserialize(&v3).unwrap().is_center_start());
+    }
+
+    #[test]
+    fn saves_from_before_lives_and_the_torus_still_load() {
+        let text = serialize(&played());
+        let v2 = text
+            .replace(HEADER, HEADER_V2)
+            .replace("lives 1 1\n", "")
+            .replace("center_start false\n", "");
+        let loaded = deserialize(&v2).unwrap();
+        assert_eq!((loaded.lives, loaded.max_lives), (1, 1));
+        let v1 = v2
+            .replace(HEADER_V2, HEADER_V1)
+            .replace("torus false\n", "");
+        let loaded = deserialize(&v1).unwrap();
+        assert!(!loaded.torus);
+        assert_eq!(loaded.unopened_safe_cells, played().unopened_safe_cells);
+        // An old header ahead of the new lines is a damaged save:
+        assert!(deserialize(&text.replace(HEADER, HEADER_V1)).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Best-time records, persisted between runs as a flat JSON object mapping a
+//! board key (`rows x cols / mines`) to the best time in milliseconds. A
+//! missing or corrupt file just means there are no records yet.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use pipebomb_sweeper::Field;
+
+pub struct Scores {
+    path: Option<PathBuf>,
+    best: BTreeMap<String, u64>,
+}
+
+/// `$XDG_DATA_HOME/pipebomb-sweeper`, falling back to `~/.local/share`:
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("pipebomb-sweeper"))
+}
+
+/// Records are kept per board configuration, so presets & custom boards with
+/// the same shape share them. Tori & spare lives make games of their own:
+pub fn board_key(field: &Field) -> String {
+    let mut key = key(field.rows(), field.cols(), field.bomb_count());
+    if field.is_torus() {
+        key.push_str("/torus");
+    }
+    if field.max_lives() > 1 {
+        key.push_str(&format!("/lives{}", field.max_lives()));
+    }
+    key
+}
+
+pub fn key(rows: usize, cols: usize, mines: usize) -> String {
+    format!("{}x{}/{}", rows, cols, mines)
+}
+
+/// Parses `{"key": 123, ...}`. Returns None on anything unexpected:
+pub fn parse<T: FromStr>(text: &str) -> Option<BTreeMap<String, T>> {
+    let mut best = BTreeMap::new();
+    let body = text.trim().strip_prefix('{')?.strip_suffix('}')?.trim();
+    if body.is_empty() {
+        return Some(best);
+    }
+    for entry in body.split(',') {
+        let (key, value) = entry.split_once(':')?;
+        let key = key.trim().strip_prefix('"')?.strip_suffix('"')?;
+        best.insert(key.to_owned(), value.trim().parse().ok()?);
+    }
+    Some(best)
+}
+
+fn serialize(best: &BTreeMap<String, u64>) -> String {
+    let entries: Vec<String> = best
+        .iter()
+        .map(|(key, ms)| format!("  \"{}\": {}", key, ms))
+        .collect();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+impl Scores {
+    pub fn load() -> Self {
+        let path = data_dir().map(|dir| dir.join("scores.json"));
+        let best = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| parse(&text))
+            .unwrap_or_default();
+        Scores { path, best }
+    }
+
+    pub fn best(&self, key: &str) -> Option<Duration> {
+        self.best.get(key).map(|&ms| Duration::from_millis(ms))
+    }
+
+    /// Stores `time` if it beats the current record for `key`. Returns true
+    /// if it was a new record:
+    pub fn record(&mut self, key: &str, time: Duration) -> bool {
+        let ms = time.as_millis() as u64;
+        if self.best.get(key).is_some_and(|&best| best <= ms) {
+            return false;
+        }
+        self.best.insert(key.to_owned(), ms);
+        if let Some(path) = &self.path {
+            // Failing to save shouldn't interrupt the game:
+            let _ = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path, serialize(&self.best)));
+        }
+        true
+    }
+}
@@ -0,0 +1,108 @@
+//! Incremental terminal output. Each frame is a list of lines made of chunks
+//! placed at known columns; only chunks that differ from the previous frame
+//! are rewritten, so a keystroke usually costs a few cursor moves instead of
+//! a full clear & reprint.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// Text drawn starting at a 1-based terminal column:
+#[derive(Clone, PartialEq)]
+pub struct Chunk {
+    pub col: usize,
+    pub text: String,
+}
+
+pub type Line = Vec<Chunk>;
+
+/// Text starting at the left margin:
+pub fn text_line(text: String) -> Line {
+    vec![Chunk { col: 1, text }]
+}
+
+/// `frame` as plain lines of text, for output that isn't a terminal. Chunks
+/// must be free of escape sequences for the columns to line up:
+pub fn plain(frame: &[Line]) -> String {
+    let mut text = String::new();
+    for line in frame {
+        let mut row = String::new();
+        for chunk in line {
+            let width = crate::width::width(&row);
+            row.extend(std::iter::repeat_n(
+                ' ',
+                (chunk.col - 1).saturating_sub(width),
+            ));
+            row.push_str(&chunk.text);
+        }
+        text.push_str(row.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+pub struct Screen {
+    prev: Vec<Line>,
+    // Forces the next frame to clear the screen & draw everything:
+    stale: bool,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Screen {
+            prev: Vec::new(),
+            stale: true,
+        }
+    }
+
+    /// Makes the next `draw` a full redraw, e.g. after a reset:
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Draws `frame`, rewriting only what changed since the last one. Leaves
+    /// the terminal cursor on the line after the frame, with everything below
+    /// erased, so prompts & messages printed there don't linger:
+    pub fn draw(&mut self, frame: Vec<Line>) {
+        let mut out = String::new();
+        if self.stale {
+            out.push_str("\x1b[2J");
+            self.prev.clear();
+            self.stale = false;
+        }
+        for (i, line) in frame.iter().enumerate() {
+            let row = i + 1;
+            match self.prev.get(i) {
+                Some(prev) if prev == line => {}
+                // Same layout, so only the chunks that changed need rewriting.
+                // The last one may have shrunk, so the rest of its line is
+                // erased:
+                Some(prev) if same_layout(prev, line) => {
+                    for (j, (old, new)) in prev.iter().zip(line).enumerate() {
+                        if old.text != new.text {
+                            let _ = write!(out, "\x1b[{};{}H{}", row, new.col, new.text);
+                            if j + 1 == line.len() {
+                                out.push_str("\x1b[K");
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    let _ = write!(out, "\x1b[{};1H\x1b[K", row);
+                    for chunk in line {
+                        let _ = write!(out, "\x1b[{};{}H{}", row, chunk.col, chunk.text);
+                    }
+                }
+            }
+        }
+        let _ = write!(out, "\x1b[{};1H\x1b[J", frame.len() + 1);
+        self.prev = frame;
+
+        let mut stdout = std::io::stdout();
+        stdout.write_all(out.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+fn same_layout(a: &Line, b: &Line) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.col == b.col)
+}
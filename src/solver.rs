@@ -0,0 +1,597 @@
+//! Logical deduction over a `Field`: single-point rules (a number whose mines
+//! are all found makes its other neighbors safe, a number with exactly as
+//! many unknown neighbors as missing mines makes them all mines) plus subset
+//! elimination between pairs of numbers. Only information a player could see
+//! is used, never the hidden bomb layout of closed cells.
+//!
+//! `solve_step` plays those deductions on a live field, for the auto-solver,
+//! & `probabilities` estimates the odds once deduction runs out.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::{Field, State};
+
+/// What a player knows about a field: which cells are open & which closed
+/// cells are proven bombs.
+pub struct Knowledge {
+    rows: usize,
+    cols: usize,
+    open: Vec<Vec<bool>>,
+    mine: Vec<Vec<bool>>,
+}
+
+/// Cells that can be proven safe or proven to hold a bomb.
+#[derive(Default)]
+pub struct Deductions {
+    pub safe: BTreeSet<(usize, usize)>,
+    pub mines: BTreeSet<(usize, usize)>,
+}
+
+impl Deductions {
+    pub fn is_empty(&self) -> bool {
+        self.safe.is_empty() && self.mines.is_empty()
+    }
+}
+
+impl Knowledge {
+    /// Nothing opened & nothing known:
+    pub fn blank(field: &Field) -> Self {
+        Knowledge {
+            rows: field.rows(),
+            cols: field.cols(),
+            open: vec![vec![false; field.cols()]; field.rows()],
+            mine: vec![vec![false; field.cols()]; field.rows()],
+        }
+    }
+
+    /// The open cells of `field` as the player currently sees them. Flags are
+    /// the player's guesses, so they aren't trusted as proven bombs, but
+    /// bombs that went off are:
+    pub fn from_field(field: &Field) -> Self {
+        let mut knowledge = Knowledge::blank(field);
+        for row in 0..field.rows() {
+            for col in 0..field.cols() {
+                if field.is_detonated(row, col) {
+                    knowledge.mine[row][col] = true;
+                } else {
+                    knowledge.open[row][col] = field.cell(row, col).state == State::Open;
+                }
+            }
+        }
+        knowledge
+    }
+
+    pub fn is_open(&self, row: usize, col: usize) -> bool {
+        self.open[row][col]
+    }
+
+    pub fn is_mine(&self, row: usize, col: usize) -> bool {
+        self.mine[row][col]
+    }
+
+    pub fn mark_mine(&mut self, row: usize, col: usize) {
+        self.mine[row][col] = true;
+    }
+
+    /// Opens a cell the way the game would, flood filling zero regions, using
+    /// `field`'s bombs to learn the revealed numbers. Returns false if the
+    /// cell is a bomb:
+    pub fn open(&mut self, field: &Field, row: usize, col: usize) -> bool {
+        if field.has_bomb_at(row, col) {
+            return false;
+        }
+        let mut queue = VecDeque::new();
+        queue.push_back((row, col));
+        while let Some((row, col)) = queue.pop_front() {
+            if self.open[row][col] || field.has_bomb_at(row, col) {
+                continue;
+            }
+            self.open[row][col] = true;
+            if field.bombs_around(row, col) == 0 {
+                queue.extend(field.neighbors(row, col));
+            }
+        }
+        true
+    }
+
+    /// Number of safe cells not yet opened:
+    pub fn unopened_safe(&self, field: &Field) -> usize {
+        let mut count = 0;
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if !self.open[row][col] && !field.has_bomb_at(row, col) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// For every open number with undecided neighbors: those neighbors & how
+    /// many bombs are still missing among them.
+    fn constraints(&self, field: &Field) -> Vec<(BTreeSet<(usize, usize)>, usize)> {
+        let mut constraints = Vec::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if !self.open[row][col] {
+                    continue;
+                }
+                let mut unknown = BTreeSet::new();
+                let mut found = 0;
+                for (r, c) in field.neighbors(row, col) {
+                    if self.mine[r][c] {
+                        found += 1;
+                    } else if !self.open[r][c] {
+                        unknown.insert((r, c));
+                    }
+                }
+                if !unknown.is_empty() {
+                    let count = field.bombs_around(row, col) as usize;
+                    constraints.push((unknown, count.saturating_sub(found)));
+                }
+            }
+        }
+        constraints
+    }
+
+    /// Runs one pass of the single-point & subset rules:
+    pub fn deduce(&self, field: &Field) -> Deductions {
+        let mut deductions = Deductions::default();
+        let constraints = self.constraints(field);
+
+        for (unknown, missing) in &constraints {
+            if *missing == 0 {
+                deductions.safe.extend(unknown);
+            } else if *missing == unknown.len() {
+                deductions.mines.extend(unknown);
+            }
+        }
+        if !deductions.is_empty() {
+            return deductions;
+        }
+
+        // Subset elimination: if A's cells are all among B's, the cells only
+        // in B hold exactly B's missing mines minus A's:
+        for (a, a_missing) in &constraints {
+            for (b, b_missing) in &constraints {
+                if a.len() >= b.len() || !a.is_subset(b) {
+                    continue;
+                }
+                let rest: Vec<_> = b.difference(a).collect();
+                let rest_missing = b_missing.saturating_sub(*a_missing);
+                if rest_missing == 0 {
+                    deductions.safe.extend(rest);
+                } else if rest_missing == rest.len() {
+                    deductions.mines.extend(rest);
+                }
+            }
+        }
+        deductions
+    }
+}
+
+/// True if a player opening `(row, col)` first can clear the whole field by
+/// deduction alone, without ever having to guess:
+pub fn is_solvable(field: &Field, row: usize, col: usize) -> bool {
+    let mut knowledge = Knowledge::blank(field);
+    if !knowledge.open(field, row, col) {
+        return false;
+    }
+    loop {
+        if knowledge.unopened_safe(field) == 0 {
+            return true;
+        }
+        let deductions = knowledge.deduce(field);
+        if deductions.is_empty() {
+            return false;
+        }
+        for (r, c) in deductions.mines {
+            knowledge.mark_mine(r, c);
+        }
+        for (r, c) in deductions.safe {
+            knowledge.open(field, r, c);
+        }
+    }
+}
+
+/// What a `solve_step` got to:
+#[derive(PartialEq)]
+pub enum Progress {
+    /// Some cells were flagged or opened:
+    Advanced,
+    /// Every safe cell is open:
+    Solved,
+    /// Nothing more can be proven; only a guess would get further:
+    Stuck,
+}
+
+/// Plays one round of deduction on `field`: flags the proven mines, then
+/// opens the proven-safe cells, moving the cursor onto each. Never guesses,
+/// so it never hits a bomb. The player's own flags are ignored, but opening
+/// a proven-safe cell clears a wrong one:
+pub fn solve_step(field: &mut Field) -> Progress {
+    if field.victory() {
+        return Progress::Solved;
+    }
+    // Before the first open there's nothing to deduce from:
+    if field.is_first_move() {
+        return Progress::Stuck;
+    }
+    let mut knowledge = Knowledge::from_field(field);
+    let mut advanced = false;
+    loop {
+        let deductions = knowledge.deduce(field);
+        if deductions.is_empty() {
+            return if advanced {
+                Progress::Advanced
+            } else {
+                Progress::Stuck
+            };
+        }
+        for &(row, col) in &deductions.mines {
+            knowledge.mark_mine(row, col);
+            advanced |= field.cell(row, col).state != State::Flagged;
+            field.flag_at(row, col);
+        }
+        // Known mines only feed back into the next pass, so keep deducing
+        // until something can be opened:
+        if deductions.safe.is_empty() {
+            continue;
+        }
+        for (row, col) in deductions.safe {
+            if field.cell(row, col).state != State::Open {
+                field.set_cursor(row, col);
+                field.open_at_cursor();
+            }
+        }
+        return if field.victory() {
+            Progress::Solved
+        } else {
+            Progress::Advanced
+        };
+    }
+}
+
+/// A closed cell proven safe by the simplest rule a player would use: an open
+/// number whose flags already match it makes its other closed neighbors safe.
+/// Unlike `Knowledge`, this trusts the player's flags:
+pub fn safe_hint(field: &Field) -> Option<(usize, usize)> {
+    for row in 0..field.rows() {
+        for col in 0..field.cols() {
+            if field.cell(row, col).state != State::Open || field.is_detonated(row, col) {
+                continue;
+            }
+            let neighbors = field.neighbors(row, col);
+            let flagged = neighbors
+                .iter()
+                .filter(|&&(r, c)| {
+                    field.cell(r, c).state == State::Flagged || field.is_detonated(r, c)
+                })
+                .count() as u32;
+            if flagged != field.bombs_around(row, col) {
+                continue;
+            }
+            let safe = neighbors.into_iter().find(|&(r, c)| {
+                matches!(field.cell(r, c).state, State::Closed | State::Questioned)
+            });
+            if safe.is_some() {
+                return safe;
+            }
+        }
+    }
+    None
+}
+
+// Frontier components with more cells than this aren't enumerated; their
+// cells get a local estimate instead:
+const MAX_ENUMERATED: usize = 24;
+
+/// Every consistent way to place mines in one connected part of the
+/// frontier, grouped by how many mines it uses: for each count, how many
+/// placements there are & how many of them put a mine on each cell.
+struct Component {
+    cells: Vec<(usize, usize)>,
+    // Indexed by mine count:
+    placements: Vec<f64>,
+    mines_per_cell: Vec<Vec<f64>>,
+}
+
+/// Enumerates the placements of `cells` satisfying `constraints`, each a
+/// set of indices into `cells` & the mines it needs among them:
+fn enumerate(cells: Vec<(usize, usize)>, constraints: &[(Vec<usize>, usize)]) -> Component {
+    let n = cells.len();
+    let mut component = Component {
+        cells,
+        placements: vec![0.0; n + 1],
+        mines_per_cell: vec![vec![0.0; n]; n + 1],
+    };
+    // The constraints each cell is part of, the only ones its value can
+    // break:
+    let mut containing = vec![Vec::new(); n];
+    for (i, (members, _)) in constraints.iter().enumerate() {
+        for &m in members {
+            containing[m].push(i);
+        }
+    }
+    let mut mine = vec![false; n];
+    fn assign(
+        i: usize,
+        mine: &mut Vec<bool>,
+        constraints: &[(Vec<usize>, usize)],
+        containing: &[Vec<usize>],
+        component: &mut Component,
+    ) {
+        if i == mine.len() {
+            let count = mine.iter().filter(|&&m| m).count();
+            component.placements[count] += 1.0;
+            for (cell, &m) in mine.iter().enumerate() {
+                if m {
+                    component.mines_per_cell[count][cell] += 1.0;
+                }
+            }
+            return;
+        }
+        for value in [false, true] {
+            mine[i] = value;
+            // Cells up to `i` are decided: their mines mustn't exceed what a
+            // constraint needs, nor leave more than its undecided cells can
+            // still hold:
+            let feasible = containing[i].iter().all(|&c| {
+                let (members, needed) = &constraints[c];
+                let placed = members.iter().filter(|&&m| m <= i && mine[m]).count();
+                let open = members.iter().filter(|&&m| m > i).count();
+                placed <= *needed && placed + open >= *needed
+            });
+            if feasible {
+                assign(i + 1, mine, constraints, containing, component);
+            }
+        }
+        mine[i] = false;
+    }
+    assign(0, &mut mine, constraints, &containing, &mut component);
+    component
+}
+
+/// Multiplies two mine-count distributions:
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut out = vec![0.0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            out[i + j] += x * y;
+        }
+    }
+    out
+}
+
+/// `ln(n!)` for every `n` up to `max`:
+fn ln_factorials(max: usize) -> Vec<f64> {
+    let mut table = vec![0.0; max + 1];
+    for n in 1..=max {
+        table[n] = table[n - 1] + (n as f64).ln();
+    }
+    table
+}
+
+/// Estimated chance that each unopened cell holds a mine, from what the
+/// player can see. Cells next to open numbers are weighed over every mine
+/// placement consistent with those numbers & the total mine count; the rest
+/// share the mines left over. Player flags aren't trusted, so flagged cells
+/// get a chance too:
+pub fn probabilities(field: &Field) -> HashMap<(usize, usize), f64> {
+    let mut odds = HashMap::new();
+    let knowledge = Knowledge::from_field(field);
+    let cells = || (0..field.rows()).flat_map(|row| (0..field.cols()).map(move |col| (row, col)));
+    // Bombs that went off are the only ones known for sure:
+    let detonated = cells()
+        .filter(|&(row, col)| knowledge.is_mine(row, col))
+        .count();
+    let unopened: Vec<(usize, usize)> = cells()
+        .filter(|&(row, col)| !knowledge.is_open(row, col) && !knowledge.is_mine(row, col))
+        .collect();
+    if unopened.is_empty() {
+        return odds;
+    }
+    let constraints = if field.is_first_move() {
+        Vec::new()
+    } else {
+        knowledge.constraints(field)
+    };
+
+    // Split the frontier into parts that share no constraint:
+    let mut part: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut parts: Vec<Vec<(usize, usize)>> = Vec::new();
+    for (unknown, _) in &constraints {
+        let touched: BTreeSet<usize> = unknown
+            .iter()
+            .filter_map(|c| part.get(c).copied())
+            .collect();
+        let mut merged: Vec<(usize, usize)> = unknown.iter().copied().collect();
+        for &p in touched.iter().rev() {
+            merged.append(&mut parts[p]);
+        }
+        merged.sort_unstable();
+        merged.dedup();
+        let index = parts.len();
+        for &cell in &merged {
+            part.insert(cell, index);
+        }
+        parts.push(merged);
+    }
+
+    let mut components = Vec::new();
+    let mut approximated_mines = 0.0;
+    for cells in parts.into_iter().filter(|cells| !cells.is_empty()) {
+        let index: HashMap<(usize, usize), usize> =
+            cells.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let local: Vec<(Vec<usize>, usize)> = constraints
+            .iter()
+            .filter(|(unknown, _)| unknown.iter().any(|c| index.contains_key(c)))
+            .map(|(unknown, missing)| (unknown.iter().map(|c| index[c]).collect(), *missing))
+            .collect();
+        if cells.len() <= MAX_ENUMERATED {
+            components.push(enumerate(cells, &local));
+            continue;
+        }
+        // Too big to enumerate: each cell takes the worst odds of the numbers
+        // around it:
+        for (i, &cell) in cells.iter().enumerate() {
+            let chance = local
+                .iter()
+                .filter(|(members, _)| members.contains(&i))
+                .map(|(members, missing)| *missing as f64 / members.len() as f64)
+                .fold(0.0, f64::max);
+            approximated_mines += chance;
+            odds.insert(cell, chance);
+        }
+    }
+
+    let frontier: usize = components.iter().map(|c| c.cells.len()).sum::<usize>() + odds.len();
+    let others = unopened.len() - frontier;
+    let mines = (field.bomb_count() as f64 - detonated as f64 - approximated_mines)
+        .round()
+        .max(0.0) as usize;
+    let ln_fact = ln_factorials(others);
+    // Ways to put the mines the frontier leaves over in the other cells, on
+    // a log scale:
+    let ln_ways = |frontier_mines: usize| -> Option<f64> {
+        let rest = mines.checked_sub(frontier_mines)?;
+        (rest <= others).then(|| ln_fact[others] - ln_fact[rest] - ln_fact[others - rest])
+    };
+    let total = components
+        .iter()
+        .fold(vec![1.0], |acc, c| convolve(&acc, &c.placements));
+    let max_ln = (0..total.len())
+        .filter(|&k| total[k] > 0.0)
+        .filter_map(ln_ways)
+        .fold(f64::NEG_INFINITY, f64::max);
+    if max_ln == f64::NEG_INFINITY {
+        // No placement fits the mine count, which only wrong numbers could
+        // cause:
+        return odds;
+    }
+    let weight = |k: usize| ln_ways(k).map_or(0.0, |ln| (ln - max_ln).exp());
+    let norm: f64 = (0..total.len()).map(|k| total[k] * weight(k)).sum();
+
+    for (i, component) in components.iter().enumerate() {
+        let rest = components
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(vec![1.0], |acc, (_, c)| convolve(&acc, &c.placements));
+        for (cell_index, &cell) in component.cells.iter().enumerate() {
+            let mut sum = 0.0;
+            for (k, per_cell) in component.mines_per_cell.iter().enumerate() {
+                for (r, &ways) in rest.iter().enumerate() {
+                    sum += per_cell[cell_index] * ways * weight(k + r);
+                }
+            }
+            odds.insert(cell, sum / norm);
+        }
+    }
+    if others > 0 {
+        let expected: f64 = (0..total.len())
+            .map(|k| total[k] * weight(k) * mines.saturating_sub(k) as f64)
+            .sum::<f64>()
+            / norm;
+        for &cell in &unopened {
+            odds.entry(cell).or_insert(expected / others as f64);
+        }
+    }
+    odds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hand-laid board, opened at each of `opens` in turn:
+    fn opened(
+        rows: usize,
+        cols: usize,
+        bombs: &[(usize, usize)],
+        opens: &[(usize, usize)],
+    ) -> Field {
+        let mut field = Field::with_bombs(rows, cols, bombs, 0);
+        field.place_bombs_excluding(opens[0].0, opens[0].1);
+        for &(row, col) in opens {
+            field.set_cursor(row, col);
+            field.open_at_cursor();
+        }
+        field
+    }
+
+    #[test]
+    fn a_satisfied_number_proves_its_neighbors() {
+        // `*1.` with the `.` opened floods onto the 1, whose only closed
+        // neighbor must be the bomb:
+        let field = opened(1, 3, &[(0, 0)], &[(0, 2)]);
+        let deductions = Knowledge::from_field(&field).deduce(&field);
+        assert_eq!(deductions.mines, BTreeSet::from([(0, 0)]));
+        assert!(deductions.safe.is_empty());
+    }
+
+    #[test]
+    fn subsets_prove_what_single_numbers_cannot() {
+        // Three open 1s over a closed row with the bomb in the middle: no
+        // number alone decides anything, but the edge 1s are subsets of the
+        // middle one:
+        let field = opened(2, 3, &[(1, 1)], &[(0, 0), (0, 1), (0, 2)]);
+        let deductions = Knowledge::from_field(&field).deduce(&field);
+        assert_eq!(deductions.safe, BTreeSet::from([(1, 0), (1, 2)]));
+        assert!(deductions.mines.is_empty());
+    }
+
+    #[test]
+    fn solvability_needs_deduction_all_the_way() {
+        // Opening on a bomb solves nothing:
+        let field = Field::with_bombs(2, 3, &[(1, 1)], 0);
+        assert!(!is_solvable(&field, 1, 1));
+        // From a corner 1 the other corner is a coin toss:
+        let field = Field::with_bombs(2, 2, &[(0, 0)], 0);
+        assert!(!is_solvable(&field, 1, 1));
+        let field = Field::with_bombs(1, 3, &[(0, 0)], 0);
+        assert!(is_solvable(&field, 0, 2));
+    }
+
+    #[test]
+    fn solve_step_plays_until_it_must_guess() {
+        let mut field = opened(2, 3, &[(1, 1)], &[(0, 0), (0, 1), (0, 2)]);
+        assert!(solve_step(&mut field) == Progress::Solved);
+        assert!(field.victory());
+
+        let mut field = opened(2, 2, &[(0, 0)], &[(1, 1)]);
+        assert!(solve_step(&mut field) == Progress::Stuck);
+        assert_eq!(field.opened_count(), 1);
+
+        let mut field = Field::with_bombs(1, 3, &[(0, 0)], 0);
+        assert!(solve_step(&mut field) == Progress::Stuck);
+    }
+
+    #[test]
+    fn hints_trust_the_players_flags() {
+        let mut field = opened(1, 3, &[(0, 0)], &[(0, 1)]);
+        assert_eq!(safe_hint(&field), None);
+        field.flag_at(0, 0);
+        assert_eq!(safe_hint(&field), Some((0, 2)));
+    }
+
+    #[test]
+    fn odds_are_certain_where_deduction_is() {
+        let field = opened(2, 3, &[(1, 1)], &[(0, 0), (0, 1), (0, 2)]);
+        let odds = probabilities(&field);
+        assert_eq!(odds.len(), 3);
+        assert!(odds[&(1, 0)].abs() < 1e-9);
+        assert!((odds[&(1, 1)] - 1.0).abs() < 1e-9);
+        assert!(odds[&(1, 2)].abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_coin_toss_is_even_odds() {
+        // Two open 1s under two closed cells, one of them the bomb:
+        let field = opened(2, 2, &[(0, 0)], &[(1, 0), (1, 1)]);
+        let odds = probabilities(&field);
+        assert_eq!(odds.len(), 2);
+        assert!((odds[&(0, 0)] - 0.5).abs() < 1e-9);
+        assert!((odds[&(0, 1)] - 0.5).abs() < 1e-9);
+    }
+}
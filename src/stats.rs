@@ -0,0 +1,101 @@
+//! Lifetime statistics, persisted between runs as a flat JSON object next to
+//! the best times. A missing or corrupt file just starts them afresh.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::args::Difficulty;
+use crate::scores::{self, Scores};
+
+pub struct Stats {
+    path: Option<PathBuf>,
+    played: u64,
+    won: u64,
+    time_ms: u64,
+    // Best 3BV cleared per second of a won game:
+    best_3bv_per_s: f64,
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        let path = scores::data_dir().map(|dir| dir.join("stats.json"));
+        let values = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| scores::parse::<f64>(&text))
+            .unwrap_or_default();
+        let count = |key: &str| values.get(key).map_or(0, |&value| value as u64);
+        Stats {
+            played: count("played"),
+            won: count("won"),
+            time_ms: count("time_ms"),
+            best_3bv_per_s: values.get("best_3bv_per_s").copied().unwrap_or(0.0),
+            path,
+        }
+    }
+
+    /// Counts a finished game that took `time`. Only wins can set the best
+    /// 3BV/s:
+    pub fn record(&mut self, won: bool, time: Duration, three_bv: usize) {
+        self.played += 1;
+        self.time_ms += time.as_millis() as u64;
+        if won {
+            self.won += 1;
+            let secs = time.as_secs_f64();
+            if secs > 0.0 {
+                self.best_3bv_per_s = self.best_3bv_per_s.max(three_bv as f64 / secs);
+            }
+        }
+        if let Some(path) = &self.path {
+            // Failing to save shouldn't interrupt the game:
+            let _ = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path, self.serialize()));
+        }
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "{{\n  \"played\": {},\n  \"won\": {},\n  \"time_ms\": {},\n  \"best_3bv_per_s\": {}\n}}\n",
+            self.played, self.won, self.time_ms, self.best_3bv_per_s
+        )
+    }
+
+    /// The statistics as printed by `--stats`, with the fastest time of
+    /// each preset from `scores`:
+    pub fn report(&self, scores: &Scores) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "Games played: {}", self.played);
+        let rate = match self.played {
+            0 => 0.0,
+            played => self.won as f64 * 100.0 / played as f64,
+        };
+        let _ = writeln!(text, "Games won: {} ({:.1}%)", self.won, rate);
+        let secs = self.time_ms / 1000;
+        let _ = writeln!(
+            text,
+            "Time played: {}h {:02}m {:02}s",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        let _ = writeln!(text, "Best 3BV/s: {:.2}", self.best_3bv_per_s);
+        text.push_str("Fastest times:");
+        for difficulty in [
+            Difficulty::Beginner,
+            Difficulty::Intermediate,
+            Difficulty::Expert,
+        ] {
+            let (rows, cols, mines) = difficulty.dimensions();
+            let best = match scores.best(&scores::key(rows, cols, mines)) {
+                Some(best) => format!("{:.1}s", best.as_secs_f64()),
+                None => "-".to_owned(),
+            };
+            let _ = write!(text, "\n  {}: {}", difficulty.name(), best);
+        }
+        text
+    }
+}
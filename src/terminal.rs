@@ -0,0 +1,312 @@
+//! Platform-specific terminal handling: raw, unechoed key input that's
+//! restored on drop, unbuffered byte reads with a readiness check (to tell
+//! escape sequences apart from a bare Escape), the window size & its changes
+//! and a graceful termination request. Output is plain ANSI on every platform.
+
+use std::io::Write;
+
+#[cfg(unix)]
+pub use unix::*;
+#[cfg(windows)]
+pub use windows::*;
+
+/// Turns on SGR mouse reporting (`ESC [ < b ; x ; y M`) while alive:
+pub struct MouseCapture;
+
+impl MouseCapture {
+    pub fn enable() -> Self {
+        print!("\x1b[?1000h\x1b[?1006h");
+        let _ = std::io::stdout().flush();
+        MouseCapture
+    }
+}
+
+impl Drop for MouseCapture {
+    fn drop(&mut self) {
+        print!("\x1b[?1006l\x1b[?1000l");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use termios::{tcsetattr, Termios, ECHO, ICANON, ISIG, TCSANOW};
+
+    const STDIN_FILENO: libc::c_int = 0;
+
+    static SIGTERM_RECEIVED: AtomicBool = AtomicBool::new(false);
+    static SIGWINCH_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+    /// Holds the original terminal attributes & restores them when dropped,
+    /// so the terminal is usable again even after a panic or an early return:
+    pub struct RawMode {
+        og_attr: Termios,
+    }
+
+    impl RawMode {
+        /// Sets non-canonical mode. Signals are disabled too, so Ctrl-C
+        /// arrives as a byte & quits through the normal restore path:
+        pub fn enable() -> io::Result<Self> {
+            let og_attr = Termios::from_fd(STDIN_FILENO)?;
+            let mut new_attr = og_attr;
+            new_attr.c_lflag &= !(ICANON | ECHO | ISIG);
+            tcsetattr(STDIN_FILENO, TCSANOW, &new_attr)?;
+            Ok(RawMode { og_attr })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            let _ = tcsetattr(STDIN_FILENO, TCSANOW, &self.og_attr);
+        }
+    }
+
+    extern "C" fn on_sigterm(_: libc::c_int) {
+        SIGTERM_RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    /// True once a SIGTERM asked the game to exit:
+    pub fn terminated() -> bool {
+        SIGTERM_RECEIVED.load(Ordering::SeqCst)
+    }
+
+    /// Catches SIGTERM without `SA_RESTART`, so a blocked `read_byte` wakes
+    /// up & the game can autosave & restore the terminal on its way out:
+    pub fn handle_terminate() {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigterm as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut());
+        }
+    }
+
+    extern "C" fn on_sigwinch(_: libc::c_int) {
+        SIGWINCH_RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    /// True if the window was resized since the last call:
+    pub fn take_resized() -> bool {
+        SIGWINCH_RECEIVED.swap(false, Ordering::SeqCst)
+    }
+
+    /// Catches SIGWINCH, which interrupts a `stdin_ready` wait so the game
+    /// can redraw for the new size:
+    pub fn handle_resize() {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigwinch as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+        }
+    }
+
+    /// Reads a single byte straight from the stdin fd. `std::io::stdin()`
+    /// buffers ahead, which would hide pending bytes from `stdin_ready`:
+    pub fn read_byte(buffer: &mut [u8]) -> io::Result<()> {
+        loop {
+            let n =
+                unsafe { libc::read(STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, 1) };
+            match n {
+                1 => return Ok(()),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    // Only a SIGTERM should interrupt waiting for a key:
+                    if e.kind() == io::ErrorKind::Interrupted && !terminated() {
+                        continue;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Returns true if stdin has a byte ready within `timeout_ms`, or whenever
+    /// one arrives if it's negative. A signal cuts the wait short:
+    pub fn stdin_ready(timeout_ms: i32) -> bool {
+        let mut fds = libc::pollfd {
+            fd: STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fds, 1, timeout_ms) > 0 }
+    }
+
+    /// `(rows, cols)` of the terminal, if stdout is one:
+    pub fn size() -> Option<(usize, usize)> {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        if ok && size.ws_row > 0 && size.ws_col > 0 {
+            Some((size.ws_row as usize, size.ws_col as usize))
+        } else {
+            None
+        }
+    }
+}
+
+/// The Console API, declared by hand to avoid pulling in a bindings crate.
+/// Virtual-terminal input makes arrow keys arrive as the same escape
+/// sequences a Unix terminal sends:
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::io;
+
+    type Handle = *mut c_void;
+
+    const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+    const ENABLE_LINE_INPUT: u32 = 0x0002;
+    const ENABLE_ECHO_INPUT: u32 = 0x0004;
+    const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const WAIT_OBJECT_0: u32 = 0;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Coord {
+        x: i16,
+        y: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct SmallRect {
+        left: i16,
+        top: i16,
+        right: i16,
+        bottom: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ConsoleScreenBufferInfo {
+        size: Coord,
+        cursor_position: Coord,
+        attributes: u16,
+        window: SmallRect,
+        maximum_window_size: Coord,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+        fn ReadFile(
+            file: Handle,
+            buffer: *mut c_void,
+            to_read: u32,
+            read: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn WaitForSingleObject(handle: Handle, milliseconds: u32) -> u32;
+        fn GetConsoleScreenBufferInfo(output: Handle, info: *mut ConsoleScreenBufferInfo) -> i32;
+    }
+
+    fn console_mode(handle: Handle) -> io::Result<u32> {
+        let mut mode = 0;
+        if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(mode)
+    }
+
+    fn set_console_mode(handle: Handle, mode: u32) -> io::Result<()> {
+        if unsafe { SetConsoleMode(handle, mode) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Holds the original console modes & restores them when dropped:
+    pub struct RawMode {
+        og_input: u32,
+        og_output: u32,
+    }
+
+    impl RawMode {
+        /// Turns off line buffering, echo & Ctrl-C processing, so Ctrl-C
+        /// arrives as a byte like on Unix, and turns on ANSI escapes:
+        pub fn enable() -> io::Result<Self> {
+            let input = unsafe { GetStdHandle(STD_INPUT_HANDLE) };
+            let output = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+            let og_input = console_mode(input)?;
+            let og_output = console_mode(output)?;
+            set_console_mode(
+                input,
+                (og_input & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT))
+                    | ENABLE_VIRTUAL_TERMINAL_INPUT,
+            )?;
+            set_console_mode(output, og_output | ENABLE_VIRTUAL_TERMINAL_PROCESSING)?;
+            Ok(RawMode {
+                og_input,
+                og_output,
+            })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), self.og_input);
+                SetConsoleMode(GetStdHandle(STD_OUTPUT_HANDLE), self.og_output);
+            }
+        }
+    }
+
+    /// There's no SIGTERM to catch on Windows:
+    pub fn terminated() -> bool {
+        false
+    }
+
+    pub fn handle_terminate() {}
+
+    /// Console resizes aren't signalled, so they show on the next key:
+    pub fn take_resized() -> bool {
+        false
+    }
+
+    pub fn handle_resize() {}
+
+    pub fn read_byte(buffer: &mut [u8]) -> io::Result<()> {
+        let mut read = 0;
+        let ok = unsafe {
+            ReadFile(
+                GetStdHandle(STD_INPUT_HANDLE),
+                buffer.as_mut_ptr() as *mut c_void,
+                1,
+                &mut read,
+                std::ptr::null_mut(),
+            )
+        };
+        match (ok, read) {
+            (0, _) => Err(io::Error::last_os_error()),
+            (_, 0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn stdin_ready(timeout_ms: i32) -> bool {
+        let input = unsafe { GetStdHandle(STD_INPUT_HANDLE) };
+        // A negative timeout becomes INFINITE:
+        unsafe { WaitForSingleObject(input, timeout_ms as u32) == WAIT_OBJECT_0 }
+    }
+
+    /// `(rows, cols)` of the console window, if stdout is one:
+    pub fn size() -> Option<(usize, usize)> {
+        let mut info = ConsoleScreenBufferInfo::default();
+        let output = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+        if unsafe { GetConsoleScreenBufferInfo(output, &mut info) } == 0 {
+            return None;
+        }
+        let rows = info.window.bottom - info.window.top + 1;
+        let cols = info.window.right - info.window.left + 1;
+        Some((rows.max(0) as usize, cols.max(0) as usize))
+    }
+}
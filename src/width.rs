@@ -0,0 +1,149 @@
+//! Terminal display width of text, for glyphs that take two columns (CJK,
+//! emoji) or none (combining marks, variation selectors, joiners). A small
+//! table covering the ranges themes are likely to use, not full Unicode
+//! East Asian Width.
+
+// Inclusive ranges of characters drawn two columns wide:
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),   // Hangul Jamo initials
+    (0x231A, 0x231B),   // watch, hourglass
+    (0x23E9, 0x23EC),   // media buttons
+    (0x23F0, 0x23F0),   // alarm clock
+    (0x23F3, 0x23F3),   // hourglass
+    (0x25FD, 0x25FE),   // small squares
+    (0x2614, 0x2615),   // umbrella, hot beverage
+    (0x2648, 0x2653),   // zodiac
+    (0x267F, 0x267F),   // wheelchair
+    (0x2693, 0x2693),   // anchor
+    (0x26A1, 0x26A1),   // high voltage
+    (0x26AA, 0x26AB),   // circles
+    (0x26BD, 0x26BE),   // soccer ball, baseball
+    (0x26C4, 0x26C5),   // snowman, sun behind cloud
+    (0x26CE, 0x26CE),   // ophiuchus
+    (0x26D4, 0x26D4),   // no entry
+    (0x26EA, 0x26EA),   // church
+    (0x26F2, 0x26F3),   // fountain, flag in hole
+    (0x26F5, 0x26F5),   // sailboat
+    (0x26FA, 0x26FA),   // tent
+    (0x26FD, 0x26FD),   // fuel pump
+    (0x2705, 0x2705),   // check mark
+    (0x270A, 0x270B),   // fists
+    (0x2728, 0x2728),   // sparkles
+    (0x274C, 0x274C),   // cross mark
+    (0x274E, 0x274E),   // cross mark button
+    (0x2753, 0x2755),   // question & exclamation marks
+    (0x2757, 0x2757),   // exclamation mark
+    (0x2795, 0x2797),   // plus, minus, divide
+    (0x27B0, 0x27B0),   // curly loop
+    (0x27BF, 0x27BF),   // double curly loop
+    (0x2B1B, 0x2B1C),   // large squares
+    (0x2B50, 0x2B50),   // star
+    (0x2B55, 0x2B55),   // circle
+    (0x2E80, 0x303E),   // CJK radicals & punctuation
+    (0x3041, 0x33FF),   // kana, CJK compatibility
+    (0x3400, 0x4DBF),   // CJK extension A
+    (0x4E00, 0x9FFF),   // CJK unified ideographs
+    (0xA000, 0xA4CF),   // Yi
+    (0xAC00, 0xD7A3),   // Hangul syllables
+    (0xF900, 0xFAFF),   // CJK compatibility ideographs
+    (0xFE30, 0xFE4F),   // CJK compatibility forms
+    (0xFF00, 0xFF60),   // fullwidth forms
+    (0xFFE0, 0xFFE6),   // fullwidth signs
+    (0x1F004, 0x1F004), // mahjong tile
+    (0x1F0CF, 0x1F0CF), // joker
+    (0x1F18E, 0x1F18E), // AB button
+    (0x1F191, 0x1F19A), // squared words
+    (0x1F200, 0x1F251), // enclosed ideographs
+    (0x1F300, 0x1F64F), // pictographs & emoticons
+    (0x1F680, 0x1F6FF), // transport & map
+    (0x1F7E0, 0x1F7EB), // large colored circles & squares
+    (0x1F90C, 0x1F9FF), // supplemental pictographs
+    (0x1FA70, 0x1FAFF), // symbols & pictographs extended A
+    (0x20000, 0x3FFFD), // CJK extensions B onwards
+];
+
+// Inclusive ranges of characters that take no column of their own:
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F),   // combining diacritics
+    (0x200B, 0x200F),   // zero-width space & joiners
+    (0x20D0, 0x20FF),   // combining marks for symbols
+    (0xFE00, 0xFE0F),   // variation selectors
+    (0x1F3FB, 0x1F3FF), // skin tone modifiers
+    (0xE0100, 0xE01EF), // variation selectors supplement
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges
+        .iter()
+        .any(|&(start, end)| (start..=end).contains(&c))
+}
+
+/// Columns `c` takes in a terminal:
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(c, ZERO) {
+        0
+    } else if in_ranges(c, WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns `text` takes in a terminal. Must be free of escape sequences:
+pub fn width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// `text` followed by enough spaces to fill `columns`:
+pub fn pad(text: &str, columns: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(columns.saturating_sub(width(text)))
+    )
+}
+
+/// The longest start of `text` that fits in `columns`:
+pub fn truncate(text: &str, columns: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += char_width(c);
+        if used > columns {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_narrow_and_zero_width_characters() {
+        assert_eq!(width("F"), 1);
+        assert_eq!(width("💣"), 2);
+        assert_eq!(width("🚩"), 2);
+        assert_eq!(width("雷"), 2);
+        // Selectors & combining marks ride on the character before them:
+        assert_eq!(width("♥\u{FE0F}"), 1);
+        assert_eq!(width("e\u{0301}"), 1);
+        assert_eq!(width("\x07"), 0);
+    }
+
+    #[test]
+    fn padding_counts_columns_not_characters() {
+        assert_eq!(pad("💣", 3), "💣 ");
+        assert_eq!(pad("F", 3), "F  ");
+        assert_eq!(pad("💣💣", 3), "💣💣");
+    }
+
+    #[test]
+    fn truncation_never_splits_a_wide_character() {
+        assert_eq!(truncate("a💣b", 2), "a");
+        assert_eq!(truncate("a💣b", 3), "a💣");
+        assert_eq!(truncate("a💣b", 9), "a💣b");
+        assert_eq!(truncate("💣", 0), "");
+    }
+}